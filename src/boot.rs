@@ -0,0 +1,190 @@
+use crate::model::Model;
+use std::fmt::{Display, Formatter};
+use std::{fs, io};
+
+/// A DMG boot ROM covers 0x0000-0x00FF.
+const DMG_SIZE: usize = 0x100;
+
+/// A CGB boot ROM also covers 0x0200-0x08FF. Its 0x0100-0x01FF is never mapped, the
+/// cartridge header shows through there.
+const CGB_SIZE: usize = 0x900;
+
+/// The size of the boot ROM `model` runs.
+fn boot_rom_size(model: Model) -> usize {
+    if model.is_cgb() {
+        CGB_SIZE
+    } else {
+        DMG_SIZE
+    }
+}
+
+#[derive(Debug)]
+pub enum BootRomError {
+    /// The boot ROM file could not be read.
+    Io(io::Error),
+    /// The image is neither a DMG (256 bytes) nor a CGB (2304 bytes) boot ROM.
+    BadSize(usize),
+    /// The image is for the other family: CGB and AGB boot from a CGB boot ROM, every
+    /// other model from a DMG one.
+    WrongModel { size: usize, model: Model },
+}
+
+impl Display for BootRomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BootRomError::Io(error) => write!(f, "could not read boot ROM: {}", error),
+            BootRomError::BadSize(size) => write!(
+                f,
+                "boot ROM is {} bytes, expected {} (DMG) or {} (CGB)",
+                size, DMG_SIZE, CGB_SIZE
+            ),
+            BootRomError::WrongModel { size, model } => write!(
+                f,
+                "a {} byte boot ROM can't boot a {}, it expects {} bytes",
+                size,
+                model,
+                boot_rom_size(*model)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BootRomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BootRomError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BootRomError {
+    fn from(error: io::Error) -> Self {
+        BootRomError::Io(error)
+    }
+}
+
+/// A boot ROM image laid over the start of the cartridge until the first write to 0xFF50
+/// with bit 0 set. Once unmapped it stays that way until the next reset.
+pub struct BootRom {
+    image: Vec<u8>,
+    mapped: bool,
+}
+
+impl BootRom {
+    pub fn new(image: impl Into<Vec<u8>>) -> Result<Self, BootRomError> {
+        let image = image.into();
+        if image.len() != DMG_SIZE && image.len() != CGB_SIZE {
+            return Err(BootRomError::BadSize(image.len()));
+        }
+
+        Ok(Self {
+            image,
+            mapped: true,
+        })
+    }
+
+    pub fn from(file: &str) -> Result<Self, BootRomError> {
+        BootRom::new(fs::read(file)?)
+    }
+
+    /// Fails unless this is the boot ROM `model` runs.
+    pub fn check(&self, model: Model) -> Result<(), BootRomError> {
+        if self.image.len() != boot_rom_size(model) {
+            return Err(BootRomError::WrongModel {
+                size: self.image.len(),
+                model,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The byte at `address`, or `None` where the cartridge is visible.
+    pub fn read(&self, address: u16) -> Option<u8> {
+        let address = address as usize;
+        let covered = address < DMG_SIZE
+            || (self.image.len() == CGB_SIZE && (0x200..CGB_SIZE).contains(&address));
+
+        if self.mapped && covered {
+            Some(self.image[address])
+        } else {
+            None
+        }
+    }
+
+    /// 0xFF50. Setting bit 0 unmaps the boot ROM for good.
+    pub fn write(&mut self, value: u8) {
+        if value & 1 != 0 {
+            self.mapped = false;
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    /// Maps the image again, as on power on.
+    pub fn reset(&mut self) {
+        self.mapped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_odd_sizes() {
+        assert!(matches!(
+            BootRom::new(vec![0; 0x200]),
+            Err(BootRomError::BadSize(0x200))
+        ));
+    }
+
+    #[test]
+    fn test_check_matches_the_model() {
+        let dmg = BootRom::new(vec![0; DMG_SIZE]).unwrap();
+        let cgb = BootRom::new(vec![0; CGB_SIZE]).unwrap();
+
+        assert!(dmg.check(Model::SGB2).is_ok());
+        assert!(cgb.check(Model::AGB).is_ok());
+        assert!(matches!(
+            dmg.check(Model::CGB),
+            Err(BootRomError::WrongModel {
+                size: DMG_SIZE,
+                model: Model::CGB
+            })
+        ));
+        assert!(cgb.check(Model::DMG).is_err());
+    }
+
+    #[test]
+    fn test_dmg_overlay_until_unmapped() {
+        let mut boot = BootRom::new(vec![0x31; DMG_SIZE]).unwrap();
+        assert_eq!(boot.read(0x0000), Some(0x31));
+        assert_eq!(boot.read(0x00FF), Some(0x31));
+        assert_eq!(boot.read(0x0100), None);
+        assert_eq!(boot.read(0x0200), None);
+
+        boot.write(0xFE);
+        assert!(boot.is_mapped());
+        boot.write(0x01);
+        assert_eq!(boot.read(0x0000), None);
+        boot.write(0x00);
+        assert!(!boot.is_mapped());
+
+        boot.reset();
+        assert_eq!(boot.read(0x0000), Some(0x31));
+    }
+
+    #[test]
+    fn test_cgb_overlay_skips_the_header() {
+        let boot = BootRom::new(vec![0x31; CGB_SIZE]).unwrap();
+        assert_eq!(boot.read(0x00FF), Some(0x31));
+        assert_eq!(boot.read(0x0150), None);
+        assert_eq!(boot.read(0x0200), Some(0x31));
+        assert_eq!(boot.read(0x08FF), Some(0x31));
+        assert_eq!(boot.read(0x0900), None);
+    }
+}
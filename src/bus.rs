@@ -1,9 +1,14 @@
+use crate::boot::BootRom;
 use crate::cartridge::Cartridge;
-use crate::cpu::CPU;
+use crate::dma::{Dma, DmaStatus};
+use crate::interrupts::{self, Interrupts};
+use crate::io;
+use crate::joypad::{Button, Joypad};
+use crate::memory::Memory;
+use crate::model::Model;
+use crate::ppu::PPU;
+use crate::serial::Serial;
 use crate::tpu::Timer;
-use std::sync::{Mutex, MutexGuard, OnceLock};
-
-static BUS: OnceLock<Mutex<Bus>> = OnceLock::new();
 
 struct RAM {
     wram: Vec<u8>,
@@ -41,151 +46,698 @@ impl RAM {
 
 pub struct Bus {
     cartridge: Cartridge,
+    boot_rom: Option<BootRom>,
     ram: RAM,
-    ie_register: u8,
-    serial_data: [u8; 2],
+    ppu: PPU,
+    timer: Timer,
+    joypad: Joypad,
+    serial: Serial,
+    dma: Dma,
+    interrupts: Interrupts,
+    model: Model,
+    speed: Speed,
+    /// Registers in 0xFF00-0xFF7F that no peripheral owns yet, e.g. sound.
+    io: [u8; 0x80],
+    cycles: u64,
 }
 
-impl Bus {
-    pub fn init(cartridge: Cartridge) {
-        BUS.get_or_init(|| Mutex::new(Bus::from(cartridge)));
+/// KEY1, the CGB speed switch. STOP toggles the speed once a switch has been armed.
+struct Speed {
+    double: bool,
+    armed: bool,
+}
+
+impl Speed {
+    fn new() -> Self {
+        Self {
+            double: false,
+            armed: false,
+        }
     }
 
-    pub fn get() -> MutexGuard<'static, Bus> {
-        BUS.get()
-            .expect("Bus is not initialised")
-            .lock()
-            .expect("Could not get lock on Bus")
+    fn read(&self) -> u8 {
+        0x7E | ((self.double as u8) << 7) | self.armed as u8
     }
 
-    fn from(cartridge: Cartridge) -> Self {
+    fn write(&mut self, value: u8) {
+        self.armed = value & 1 != 0;
+    }
+}
+
+impl Bus {
+    pub fn new(cartridge: Cartridge, model: Model) -> Self {
         let ram = RAM::new();
-        Self {
+        let mut bus = Self {
             cartridge,
+            boot_rom: None,
             ram,
-            ie_register: 0,
-            serial_data: [0; 2],
-        }
+            ppu: PPU::new(),
+            timer: Timer::new(model.div()),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            dma: Dma::new(),
+            interrupts: Interrupts::new(),
+            model,
+            speed: Speed::new(),
+            io: [0; 0x80],
+            cycles: 0,
+        };
+        bus.power_on();
+        bus
     }
 
-    pub fn read(&self, address: u16, cpu: &CPU) -> u16 {
-        if address < 0x8000 {
-            self.cartridge.read(address) as u16
+    pub fn read(&self, address: u16) -> u8 {
+        if let Some(value) = self.boot_rom.as_ref().and_then(|boot| boot.read(address)) {
+            value
+        } else if address < 0x8000 {
+            self.cartridge.read(address)
         } else if address < 0xA000 {
-            println!("Reading unimplemented address {:04X}", address);
-            todo!()
+            self.ppu.vram_read(address)
         } else if address < 0xC000 {
-            self.cartridge.read(address) as u16
+            self.cartridge.read(address)
         } else if address < 0xE000 {
-            self.ram.wram_read(address) as u16
+            self.ram.wram_read(address)
         } else if address < 0xFE00 {
-            println!("Reading reserved address {:04X}", address);
-            0
+            self.ram.wram_read(address - 0x2000)
         } else if address < 0xFEA0 {
-            println!("Reading unimplemented address {:04X}", address);
-            todo!()
+            self.ppu.oam_read(address)
         } else if address < 0xFF00 {
-            println!("Reading reserved address {:04X}", address);
-            0
+            self.unusable_read(address)
         } else if address < 0xFF80 {
-            self.io_read(address, &cpu) as u16
+            self.io_read(address)
         } else if address == 0xFFFF {
-            self.ie_register as u16
+            self.interrupts.read_enable()
         } else {
-            self.ram.hram_read(address) as u16
+            self.ram.hram_read(address)
         }
     }
 
-    pub fn write(&mut self, address: u16, value: u8, cpu: &mut CPU) {
+    pub fn write(&mut self, address: u16, value: u8) {
         if address < 0x8000 {
             self.cartridge.write(address, value)
         } else if address < 0xA000 {
-            println!("Unsupported write to address: {:#05x}", address);
-            todo!()
+            self.ppu.vram_write(address, value)
         } else if address < 0xC000 {
             self.cartridge.write(address, value)
         } else if address < 0xE000 {
             self.ram.wram_write(address, value)
         } else if address < 0xFE00 {
-            panic!(
-                "Cannot write to address: {:#05x} as it is in a reserved section",
-                address
-            )
+            self.ram.wram_write(address - 0x2000, value)
         } else if address < 0xFEA0 {
-            println!("Unsupported write to address: {:#05x}", address);
-            todo!()
+            self.ppu.oam_write(address, value)
         } else if address < 0xFF00 {
-            panic!(
-                "Cannot write to address: {:#05x} as it is in a reserved section",
-                address
-            )
+            // Nothing is stored here.
         } else if address < 0xFF80 {
-            self.io_write(address, value, cpu)
+            self.io_write(address, value)
         } else if address == 0xFFFF {
-            self.ie_register = value;
+            self.interrupts.write_enable(value);
         } else {
             self.ram.hram_write(address, value)
         }
     }
 
-    fn io_write(&mut self, address: u16, value: u8, cpu: &mut CPU) {
-        if address == 0xFF01 {
-            self.serial_data[0] = value;
-            return;
+    /// 0xFEA0-0xFEFF. It reads 0xFF while the PPU holds OAM. Otherwise a DMG reads 0x00,
+    /// and a CGB (revision E onwards) repeats the high nibble of the address' low byte.
+    fn unusable_read(&self, address: u16) -> u8 {
+        if self.ppu.oam_blocked() {
+            0xFF
+        } else if self.model.is_cgb() {
+            let nibble = (address as u8) & 0xF0;
+            nibble | (nibble >> 4)
+        } else {
+            0x00
         }
-        if address == 0xFF02 {
-            self.serial_data[1] = value;
+    }
+
+    /// Only the bits in the register's writable mask change, the rest keep their value.
+    /// Writes to registers with no writable bits are dropped.
+    fn io_write(&mut self, address: u16, value: u8) {
+        let register = io::lookup(address, self.model);
+        if register.writable == 0 {
             return;
         }
 
-        if (0xFF04 <= address) && (address <= 0xFF07) {
-            println!("{:#05x}", address);
-            Timer::get().write(address, value);
+        let value = (value & register.writable) | (self.io_value(address) & !register.writable);
+
+        match address {
+            0xFF00 => self.joypad.write(value),
+            0xFF01 | 0xFF02 => self.serial.write(address, value),
+            0xFF04..=0xFF07 => self.timer.write(address, value),
+            0xFF0F => self.interrupts.write(value),
+            0xFF46 => self.dma.write(value),
+            0xFF40..=0xFF4B => self.ppu.write(address, value),
+            // Only the CGB boot ROM picks the CPU mode, then KEY0 is locked.
+            0xFF4C if !self.boot_rom_mapped() => {}
+            0xFF4D => self.speed.write(value),
+            0xFF50 => {
+                if let Some(boot_rom) = self.boot_rom.as_mut() {
+                    boot_rom.write(value);
+                }
+            }
+            _ => self.io[(address - 0xFF00) as usize] = value,
+        }
+    }
+
+    /// Bits outside the register's readable mask, and every bit of an unused register,
+    /// read as 1.
+    fn io_read(&self, address: u16) -> u8 {
+        let register = io::lookup(address, self.model);
+
+        (self.io_value(address) & register.readable) | !register.readable
+    }
+
+    /// The value behind a register, before any masking.
+    fn io_value(&self, address: u16) -> u8 {
+        match address {
+            0xFF00 => self.joypad.read(),
+            0xFF01 | 0xFF02 => self.serial.read(address),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            0xFF0F => self.interrupts.read(),
+            0xFF46 => self.dma.read(),
+            0xFF40..=0xFF4B => self.ppu.read(address),
+            0xFF4D => self.speed.read(),
+            _ => self.io[(address - 0xFF00) as usize],
+        }
+    }
+
+    /// The number of M-cycles run since power on.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.ppu.frames()
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.joypad.set_button(button, pressed) {
+            self.interrupts.request(interrupts::JOY_PAD);
+        }
+    }
+
+    /// Puts every component back to its power-on state, keeping the inserted cartridge.
+    pub fn reset(&mut self) {
+        self.cartridge.reset();
+        self.ram = RAM::new();
+        self.ppu = PPU::new();
+        self.timer = Timer::new(self.model.div());
+        self.joypad = Joypad::new();
+        self.cycles = 0;
+        self.serial = Serial::new();
+        self.dma = Dma::new();
+        self.interrupts = Interrupts::new();
+        self.speed = Speed::new();
+        self.io = [0; 0x80];
+        if let Some(boot_rom) = self.boot_rom.as_mut() {
+            boot_rom.reset();
+        }
+        self.power_on();
+    }
+
+    /// With a boot ROM the registers start cleared and the boot ROM sets them up.
+    /// Without one they are left the way the boot ROM would have left them.
+    fn power_on(&mut self) {
+        if self.boot_rom.is_some() {
+            self.ppu.write(0xFF40, 0x00);
+            self.ppu.write(0xFF47, 0x00);
+            self.timer.write(0xFF04, 0x00);
             return;
         }
 
-        if address == 0xFF0F {
-            cpu.int_flags = value;
+        for (address, value) in io::post_boot(self.model) {
+            match address {
+                0xFF02 => self.serial.write(address, value),
+                0xFF0F => self.interrupts.write(value),
+                _ => self.io[(address - 0xFF00) as usize] = value,
+            }
+        }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Swaps the emulated hardware. Takes effect on the next `reset`.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    /// Inserts or removes the boot ROM. Takes effect on the next `reset`.
+    pub fn set_boot_rom(&mut self, boot_rom: Option<BootRom>) {
+        self.boot_rom = boot_rom;
+    }
+
+    pub fn boot_rom(&self) -> Option<&BootRom> {
+        self.boot_rom.as_ref()
+    }
+
+    /// Whether the boot ROM still hides the start of the cartridge.
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.as_ref().is_some_and(BootRom::is_mapped)
+    }
+
+    pub fn dma_status(&self) -> Option<DmaStatus> {
+        self.dma.status()
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    pub fn rom_bank(&self, address: u16) -> u16 {
+        self.cartridge.rom_bank(address)
+    }
+
+    /// The address OAM DMA is copying from, if `address` is on the same bus. VRAM has a
+    /// bus of its own, the cartridge and WRAM share the external one.
+    fn dma_conflict(&self, address: u16) -> Option<u16> {
+        let vram = |address| (0x8000..0xA000).contains(&address);
+
+        self.dma
+            .source()
+            .filter(|&source| address < 0xFE00 && vram(source) == vram(address))
+    }
+
+    pub fn serial_output(&self) -> &[u8] {
+        self.serial.output()
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        self.ppu.framebuffer()
+    }
+}
+
+impl Memory for Bus {
+    /// During OAM DMA, OAM reads 0xFF and the bus the DMA copies from returns the byte
+    /// being copied. The other bus, HRAM and I/O are still reachable.
+    fn read(&mut self, address: u16) -> u8 {
+        if self.dma.source().is_some() && (0xFE00..0xFF00).contains(&address) {
+            return 0xFF;
+        }
+
+        if let Some(source) = self.dma_conflict(address) {
+            return Bus::read(self, source);
+        }
+
+        if (0xFEA0..0xFF00).contains(&address) && !self.model.is_cgb() {
+            self.ppu.corrupt_oam(false);
+        }
+
+        Bus::read(self, address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if self.dma.source().is_some() && (0xFE00..0xFF00).contains(&address) {
             return;
         }
 
-        if (0xFF10 <= address) && (address <= 0xFF3F) {
-            println!("Ignore sound {:#05x}", address);
+        if self.dma_conflict(address).is_some() {
             return;
         }
 
-        println!("Unsupported IO write {:#05x}", address);
+        if (0xFEA0..0xFF00).contains(&address) && !self.model.is_cgb() {
+            self.ppu.corrupt_oam(true);
+        }
+
+        Bus::write(self, address, value)
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        Bus::read(self, address)
     }
 
-    fn io_read(&self, address: u16, cpu: &CPU) -> u8 {
-        if address == 0xFF01 {
-            return self.serial_data[0];
+    /// Runs the timer and PPU for `cycles` M-cycles. In double speed the PPU only sees
+    /// two dots per M-cycle.
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as u64;
+
+        for _ in 0..cycles {
+            if let (Some(source), Some(status)) = (self.dma.source(), self.dma.status()) {
+                let value = Bus::read(self, source);
+                self.ppu.oam_write(0xFE00 + status.copied as u16, value);
+            }
+            self.dma.tick();
+
+            for dot in 0..4 {
+                let mut requested = self.timer.tick() | self.serial.tick();
+                if !self.speed.double || dot % 2 == 0 {
+                    requested |= self.ppu.tick();
+                }
+                self.interrupts.request(requested);
+            }
+        }
+    }
+
+    fn stop(&mut self) -> bool {
+        self.timer.write(0xFF04, 0);
+
+        if !self.speed.armed {
+            return false;
+        }
+
+        self.speed.double = !self.speed.double;
+        self.speed.armed = false;
+        true
+    }
+
+    fn interrupts(&mut self) -> &mut Interrupts {
+        &mut self.interrupts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus(model: Model) -> Bus {
+        let mut rom = vec![0; 0x8000];
+        rom[0x14D] = 0xE7;
+        Bus::new(Cartridge::from_bytes(rom, None).unwrap(), model)
+    }
+
+    #[test]
+    fn test_reset_maps_rom_bank_1_again() {
+        let mut rom = vec![0; 0x10000];
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        rom[0x147] = 0x01;
+        rom[0x148] = 0x01;
+        rom[0x14D] = 0xE5;
+        let mut bus = Bus::new(Cartridge::from_bytes(rom, None).unwrap(), Model::DMG);
+
+        bus.write(0x2000, 3);
+        assert_eq!(bus.read(0x4000), 3);
+
+        bus.reset();
+        assert_eq!(bus.read(0x4000), 1);
+    }
+
+    #[test]
+    fn test_stop_resets_div() {
+        let mut bus = bus(Model::DMG);
+        bus.tick(200);
+        assert_ne!(bus.read(0xFF04), 0);
+
+        assert!(!bus.stop());
+        assert_eq!(bus.read(0xFF04), 0);
+    }
+
+    #[test]
+    fn test_key1_is_absent_on_dmg() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xFF4D, 0x01);
+
+        assert_eq!(bus.read(0xFF4D), 0xFF);
+        assert!(!bus.stop());
+    }
+
+    #[test]
+    fn test_stop_switches_speed_once_armed() {
+        let mut bus = bus(Model::CGB);
+        assert_eq!(bus.read(0xFF4D), 0x7E);
+        assert!(!bus.stop());
+
+        bus.write(0xFF4D, 0x01);
+        assert_eq!(bus.read(0xFF4D), 0x7F);
+        assert!(bus.stop());
+        assert_eq!(bus.read(0xFF4D), 0xFE);
+
+        let line = bus.read(0xFF44);
+        bus.tick(114);
+        assert_eq!(bus.read(0xFF44), line);
+        bus.tick(114);
+        assert_eq!(bus.read(0xFF44), line + 1);
+    }
+
+    #[test]
+    fn test_echo_ram_mirrors_wram() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xC123, 0x42);
+        assert_eq!(bus.read(0xE123), 0x42);
+
+        bus.write(0xFDFF, 0x24);
+        assert_eq!(bus.read(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn test_unusable_region_reads_per_model() {
+        let mut dmg = bus(Model::DMG);
+        let mut cgb = bus(Model::CGB);
+        assert_eq!(dmg.read(0xFEA5), 0xFF);
+        assert_eq!(cgb.read(0xFEA5), 0xFF);
+
+        dmg.write(0xFF40, 0x11);
+        cgb.write(0xFF40, 0x11);
+        dmg.write(0xFEA5, 0x12);
+        cgb.write(0xFEA5, 0x12);
+        assert_eq!(dmg.read(0xFEA5), 0x00);
+        assert_eq!(cgb.read(0xFEA5), 0xAA);
+        assert_eq!(cgb.read(0xFEF0), 0xFF);
+    }
+
+    /// A bus scanning the third OAM row, with the second row holding 0x11..=0x88 and the
+    /// third 0xF0s.
+    fn scanning_third_oam_row(model: Model) -> Bus {
+        let mut bus = bus(model);
+        for (offset, value) in [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]
+            .iter()
+            .enumerate()
+        {
+            bus.write(0xFE08 + offset as u16, *value);
+            bus.write(0xFE10 + offset as u16, 0xF0);
+        }
+        bus.write(0xFF40, 0x11);
+        bus.write(0xFF40, 0x91);
+        bus.tick(2);
+        bus
+    }
+
+    fn third_oam_row(bus: &Bus) -> Vec<u8> {
+        (0xFE10..0xFE18).map(|address| bus.read(address)).collect()
+    }
+
+    #[test]
+    fn test_unusable_region_trips_the_oam_bug_on_dmg() {
+        let mut bus = scanning_third_oam_row(Model::DMG);
+        Memory::write(&mut bus, 0xFEA0, 0x00);
+        assert_eq!(
+            third_oam_row(&bus),
+            [0x51, 0x62, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]
+        );
+
+        let mut bus = scanning_third_oam_row(Model::DMG);
+        bus.write(0xFE08, 0x0F);
+        assert_eq!(Memory::read(&mut bus, 0xFEA0), 0xFF);
+        assert_eq!(
+            third_oam_row(&bus),
+            [0x0F | 0x50, 0x62, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]
+        );
+
+        // Peeking and host pokes are side-effect free.
+        let mut bus = scanning_third_oam_row(Model::DMG);
+        assert_eq!(bus.peek(0xFEA0), 0xFF);
+        bus.write(0xFEA0, 0x00);
+        assert_eq!(third_oam_row(&bus), [0xF0; 8]);
+    }
+
+    #[test]
+    fn test_unusable_region_leaves_cgb_oam_alone() {
+        let mut bus = scanning_third_oam_row(Model::CGB);
+        Memory::write(&mut bus, 0xFEA0, 0x00);
+        Memory::read(&mut bus, 0xFEA0);
+        assert_eq!(third_oam_row(&bus), [0xF0; 8]);
+    }
+
+    #[test]
+    fn test_oam_dma_copies_160_bytes_after_the_start_delay() {
+        let mut bus = bus(Model::DMG);
+        for offset in 0..0xA0 {
+            bus.write(0xC100 + offset, offset as u8 ^ 0x5A);
         }
-        if address == 0xFF02 {
-            return self.serial_data[1];
+
+        Memory::write(&mut bus, 0xFF46, 0xC1);
+        bus.tick(1);
+        assert_eq!(bus.dma_status(), None);
+        bus.tick(1);
+        assert_eq!(
+            bus.dma_status(),
+            Some(DmaStatus {
+                source: 0xC100,
+                copied: 0
+            })
+        );
+
+        bus.tick(160);
+        assert_eq!(bus.dma_status(), None);
+        assert_eq!(bus.read(0xFF46), 0xC1);
+        for offset in 0..0xA0 {
+            assert_eq!(bus.read(0xFE00 + offset), offset as u8 ^ 0x5A);
         }
+    }
+
+    #[test]
+    fn test_oam_dma_blocks_the_bus_it_copies_from() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xC000, 0x11);
+        bus.write(0xC105, 0x42);
+        bus.write(0xFF80, 0x99);
+
+        Memory::write(&mut bus, 0xFF46, 0xC1);
+        bus.tick(7);
+
+        // The bus conflict returns the byte being copied, 0xC105.
+        assert_eq!(Memory::read(&mut bus, 0xC000), 0x42);
+        assert_eq!(Memory::read(&mut bus, 0x0150), 0x42);
+        assert_eq!(Memory::read(&mut bus, 0xFE00), 0xFF);
+        assert_eq!(Memory::read(&mut bus, 0xFF80), 0x99);
+        assert_eq!(Memory::read(&mut bus, 0xFF46), 0xC1);
+
+        Memory::write(&mut bus, 0xC000, 0x22);
+        Memory::write(&mut bus, 0xFF81, 0x33);
+        assert_eq!(bus.read(0xC000), 0x11);
+        assert_eq!(bus.read(0xFF81), 0x33);
+
+        bus.tick(155);
+        assert_eq!(Memory::read(&mut bus, 0xC000), 0x11);
+    }
 
-        if (0xFF04 <= address) && (address <= 0xFF07) {
-            return Timer::get().read(address);
+    #[test]
+    fn test_oam_dma_from_wram_leaves_vram_reachable() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xFF40, 0x00);
+        bus.write(0x8000, 0x11);
+        bus.write(0xC105, 0x42);
+
+        Memory::write(&mut bus, 0xFF46, 0xC1);
+        bus.tick(7);
+
+        assert_eq!(Memory::read(&mut bus, 0x8000), 0x11);
+        assert_eq!(Memory::read(&mut bus, 0x0150), 0x42);
+        Memory::write(&mut bus, 0x8001, 0x22);
+        assert_eq!(bus.read(0x8001), 0x22);
+    }
+
+    #[test]
+    fn test_oam_dma_from_vram_only_conflicts_on_vram() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xFF40, 0x00);
+        bus.write(0x8105, 0x42);
+        bus.write(0xC000, 0x11);
+
+        Memory::write(&mut bus, 0xFF46, 0x81);
+        bus.tick(7);
+
+        assert_eq!(Memory::read(&mut bus, 0x9000), 0x42);
+        assert_eq!(Memory::read(&mut bus, 0xC000), 0x11);
+        Memory::write(&mut bus, 0x9000, 0x22);
+        Memory::write(&mut bus, 0xC001, 0x33);
+        assert_eq!(bus.read(0x9000), 0x00);
+        assert_eq!(bus.read(0xC001), 0x33);
+    }
+
+    #[test]
+    fn test_unused_io_registers_read_ff_and_ignore_writes() {
+        let mut bus = bus(Model::DMG);
+        for address in [
+            0xFF03, 0xFF08, 0xFF15, 0xFF1F, 0xFF27, 0xFF4C, 0xFF57, 0xFF7F,
+        ] {
+            bus.write(address, 0x00);
+            assert_eq!(bus.read(address), 0xFF, "{:#06X}", address);
         }
+    }
 
-        if address == 0xFF0F {
-            return cpu.int_flags;
+    #[test]
+    fn test_io_registers_read_back_with_unmapped_bits_set() {
+        let mut bus = bus(Model::DMG);
+        for (address, read) in [
+            (0xFF0F, 0xE0),
+            (0xFF10, 0x80),
+            (0xFF11, 0x3F),
+            (0xFF13, 0xFF),
+            (0xFF14, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1C, 0x9F),
+            (0xFF20, 0xFF),
+            (0xFF23, 0xBF),
+            (0xFF26, 0x71),
+            (0xFF30, 0x00),
+            (0xFF50, 0xFF),
+        ] {
+            bus.write(address, 0x00);
+            assert_eq!(bus.read(address), read, "{:#06X}", address);
         }
 
-        // println!("Unsupported IO read {:#05x}", address);
-        0
+        bus.write(0xFF41, 0x00);
+        assert_eq!(bus.read(0xFF41) & 0x80, 0x80);
     }
 
-    pub fn read16(&self, address: u16, cpu: &CPU) -> u16 {
-        let lo = self.read(address, &cpu);
-        let hi = self.read(address + 1, &cpu);
+    #[test]
+    fn test_read_only_io_registers_ignore_writes() {
+        let mut bus = bus(Model::DMG);
+        bus.write(0xFF13, 0x12);
+        assert_eq!(bus.read(0xFF13), 0xFF);
 
-        lo | (hi << 8)
+        let line = bus.read(0xFF44);
+        bus.write(0xFF44, line + 1);
+        assert_eq!(bus.read(0xFF44), line);
     }
 
-    pub fn write16(&mut self, address: u16, value: u16, cpu: &mut CPU) {
-        self.write(address + 1, ((value >> 8) & 0xFF) as u8, cpu);
-        self.write(address, (value & 0xFF) as u8, cpu);
+    #[test]
+    fn test_cgb_io_registers_only_exist_on_cgb() {
+        let mut dmg = bus(Model::DMG);
+        let mut cgb = bus(Model::CGB);
+        dmg.write(0xFF70, 0x02);
+        cgb.write(0xFF70, 0x02);
+        dmg.write(0xFF68, 0x00);
+        cgb.write(0xFF68, 0x00);
+
+        assert_eq!(dmg.read(0xFF70), 0xFF);
+        assert_eq!(cgb.read(0xFF70), 0xFA);
+        assert_eq!(dmg.read(0xFF68), 0xFF);
+        assert_eq!(cgb.read(0xFF68), 0x40);
+    }
+
+    #[test]
+    fn test_io_writes_leave_bits_outside_the_writable_mask_alone() {
+        let mut bus = bus(Model::DMG);
+        // Only NR52's enable bit is writable, channel 1 stays on as the boot ROM left it.
+        assert_eq!(bus.read(0xFF26), 0xF1);
+        bus.write(0xFF26, 0x00);
+        assert_eq!(bus.read(0xFF26), 0x71);
+        bus.write(0xFF26, 0x8E);
+        assert_eq!(bus.read(0xFF26), 0xF1);
+    }
+
+    #[test]
+    fn test_cgb_only_registers_read_back_on_cgb() {
+        let mut cgb = bus(Model::CGB);
+        cgb.write(0xFF72, 0x5A);
+        cgb.write(0xFF77, 0x5A);
+        assert_eq!(cgb.read(0xFF72), 0x5A);
+        assert_eq!(cgb.read(0xFF77), 0x00);
+
+        // KEY0 is locked once there is no boot ROM.
+        cgb.write(0xFF4C, 0x04);
+        assert_eq!(cgb.read(0xFF4C), 0xF3);
+
+        let mut dmg = bus(Model::DMG);
+        dmg.write(0xFF72, 0x5A);
+        assert_eq!(dmg.read(0xFF72), 0xFF);
+    }
+
+    #[test]
+    fn test_sc_clock_speed_reads_back_on_cgb_only() {
+        let mut dmg = bus(Model::DMG);
+        let mut cgb = bus(Model::CGB);
+        dmg.write(0xFF02, 0x00);
+        cgb.write(0xFF02, 0x00);
+        assert_eq!(dmg.read(0xFF02), 0x7E);
+        assert_eq!(cgb.read(0xFF02), 0x7C);
+
+        dmg.write(0xFF02, 0x03);
+        cgb.write(0xFF02, 0x03);
+        assert_eq!(dmg.read(0xFF02), 0x7F);
+        assert_eq!(cgb.read(0xFF02), 0x7F);
     }
 }
@@ -1,8 +1,7 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::fs;
-use std::io::Write;
+use std::{fs, io};
 lazy_static! {
     static ref LIC_MAP: HashMap<&'static str, &'static str> = [
         ("00", "None"),
@@ -260,6 +259,78 @@ lazy_static! {
 
 static RAM_SIZE: [u8; 6] = [0, 0, 8, 32, 128, 64];
 
+/// The cartridge header ends at 0x14F, anything shorter cannot be a ROM.
+const HEADER_END: usize = 0x150;
+
+/// Why a ROM image could not be turned into a [`Cartridge`].
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The ROM file could not be read.
+    Io(io::Error),
+    /// The image is too short to hold a header, carries its length in bytes.
+    TooSmall(usize),
+    /// The header checksum at 0x14D does not match the header bytes.
+    BadHeaderChecksum { expected: u8, actual: u8 },
+    /// The cartridge type at 0x147 names a mapper we do not emulate.
+    UnsupportedMapper(u8),
+    /// The image length disagrees with the ROM size code at 0x148.
+    SizeMismatch { rom_size_code: u8, actual: usize },
+}
+
+impl Display for CartridgeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CartridgeError::Io(error) => write!(f, "could not read ROM: {}", error),
+            CartridgeError::TooSmall(size) => write!(
+                f,
+                "ROM is {} bytes, too small to hold a header ({} bytes)",
+                size, HEADER_END
+            ),
+            CartridgeError::BadHeaderChecksum { expected, actual } => write!(
+                f,
+                "header checksum failed: expected {:#04X}, computed {:#04X}",
+                expected, actual
+            ),
+            CartridgeError::UnsupportedMapper(code) => match CART_TYPE_MAP.get(code) {
+                Some(name) => write!(f, "unsupported cartridge type {} ({:#04X})", name, code),
+                None => write!(f, "unknown cartridge type {:#04X}", code),
+            },
+            CartridgeError::SizeMismatch {
+                rom_size_code,
+                actual,
+            } if *rom_size_code <= 8 => write!(
+                f,
+                "header declares {} KB of ROM but the image is {} bytes",
+                rom_size(*rom_size_code as u16),
+                actual
+            ),
+            CartridgeError::SizeMismatch { rom_size_code, .. } => {
+                write!(f, "invalid ROM size code {:#04X}", rom_size_code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(error: io::Error) -> Self {
+        CartridgeError::Io(error)
+    }
+}
+
+/// ROM only and MBC1 cartridges, with or without RAM.
+fn is_supported_mapper(code: u8) -> bool {
+    matches!(code, 0x00..=0x03 | 0x08 | 0x09)
+}
+
 fn rom_size(value: u16) -> u16 {
     assert!(value <= 8);
     32 * (1 << value)
@@ -269,13 +340,15 @@ fn to_string(slice: &[u8]) -> String {
     slice.iter().map(|&c| c as char).collect::<String>()
 }
 
-struct Header {
+/// The cartridge header at 0x100-0x14F. Its `Display` lists the fields worth showing.
+pub struct Header {
     entry: Vec<u8>,
     logo: Vec<u8>,
     title: String,
     licence: &'static str,
     sgb_flag: u8,
     cart_type: &'static str,
+    cart_code: u8,
     rom_size: u16,
     ram_size: u8,
     dest_code: u8,
@@ -283,45 +356,88 @@ struct Header {
 }
 
 pub struct Cartridge {
-    filename: String,
+    name: Option<String>,
     rom_size: usize,
     rom_data: Vec<u8>,
+    ram: Vec<u8>,
     header: Header,
+    mbc: Mbc1,
+}
+
+/// MBC1 bank registers. ROM only and ROM+RAM cartridges keep them at their power-on
+/// values, which maps the whole 32 KB ROM and leaves RAM enabled.
+struct Mbc1 {
+    enabled: bool,
+    ram_enabled: bool,
+    rom_bank: u8,
+    bank2: u8,
+    advanced_mode: bool,
+}
+
+impl Mbc1 {
+    fn new(cart_code: u8) -> Self {
+        let enabled = matches!(cart_code, 0x01..=0x03);
+
+        Self {
+            enabled,
+            ram_enabled: !enabled,
+            rom_bank: 1,
+            bank2: 0,
+            advanced_mode: false,
+        }
+    }
 }
 
 impl Header {
-    fn from(rom_data: &Vec<u8>) -> Self {
+    fn from(rom_data: &[u8]) -> Result<Self, CartridgeError> {
+        if rom_data.len() < HEADER_END {
+            return Err(CartridgeError::TooSmall(rom_data.len()));
+        }
+
         let entry = rom_data[0x100..=0x103].to_vec();
         let logo = rom_data[0x104..=0x133].to_vec();
         let title = to_string(&rom_data[0x134..=0x143]);
         let new_lic_code = rom_data[0x144..=0x145].to_vec();
         let sgb_flag = rom_data[0x146];
         let cart_code = rom_data[0x147];
-        let rom_size = rom_size(rom_data[0x148] as u16);
+        let rom_code = rom_data[0x148];
         let ram_code = rom_data[0x149];
         let dest_code = rom_data[0x14A];
         let old_lic_code = rom_data[0x14B];
         let version = rom_data[0x14C];
         let checksum = rom_data[0x14D];
 
+        Header::checksum(rom_data[0x134..=0x14C].to_vec(), checksum)?;
+
+        if !is_supported_mapper(cart_code) {
+            return Err(CartridgeError::UnsupportedMapper(cart_code));
+        }
+
+        if rom_code > 8 || rom_size(rom_code as u16) as usize * 1024 != rom_data.len() {
+            return Err(CartridgeError::SizeMismatch {
+                rom_size_code: rom_code,
+                actual: rom_data.len(),
+            });
+        }
+
+        let rom_size = rom_size(rom_code as u16);
         let licence = Header::get_licence(old_lic_code, new_lic_code);
         let cart_type = Header::get_type(cart_code);
         let ram_size = Header::get_ram_size(ram_code);
 
-        Header::checksum(rom_data[0x134..=0x14C].to_vec(), checksum);
-
-        Self {
+        Ok(Self {
             entry,
             logo,
             title,
             licence,
             sgb_flag,
             cart_type,
+            cart_code,
             rom_size,
             ram_size,
             dest_code,
             version,
-        }
+        })
     }
 
     fn get_ram_size(code: u8) -> u8 {
@@ -353,16 +469,21 @@ impl Header {
         }
     }
 
-    fn checksum(checksum_vec: Vec<u8>, assert_checksum: u8) -> () {
+    fn checksum(checksum_vec: Vec<u8>, assert_checksum: u8) -> Result<(), CartridgeError> {
         let mut checksum: i16 = 0;
         for num in checksum_vec {
             checksum = checksum - (num as i16) - 1;
         }
 
-        if checksum.to_be_bytes()[1] != assert_checksum {
-            println!("Checksum FAILED");
-            std::process::exit(1);
+        let actual = checksum.to_be_bytes()[1];
+        if actual != assert_checksum {
+            return Err(CartridgeError::BadHeaderChecksum {
+                expected: assert_checksum,
+                actual,
+            });
         }
+
+        Ok(())
     }
 }
 
@@ -377,35 +498,105 @@ impl Display for Header {
 }
 
 impl Cartridge {
-    pub fn from(rom_file: &str) -> Self {
-        let rom_data = match fs::read(rom_file) {
-            Ok(file) => file,
-            Err(_) => {
-                println!("Could not read ROM: {}", rom_file);
-                std::process::exit(1);
-            }
-        };
+    pub fn from(rom_file: &str) -> Result<Self, CartridgeError> {
+        let rom_data = fs::read(rom_file)?;
+        Cartridge::from_bytes(rom_data, Some(rom_file))
+    }
+
+    /// Builds a cartridge from a ROM image already in memory. `name` is only used for
+    /// display, the header title stands in when there is none.
+    pub fn from_bytes(
+        rom_data: impl Into<Vec<u8>>,
+        name: Option<&str>,
+    ) -> Result<Self, CartridgeError> {
+        let rom_data = rom_data.into();
         let rom_size = rom_data.len();
-        let filename = rom_file.to_string();
-        let header = Header::from(&rom_data);
+        let header = Header::from(&rom_data)?;
 
-        println!("Cartridge Loaded...");
-        println!("{}", header);
+        let ram = vec![0; header.ram_size as usize * 1024];
+        let mbc = Mbc1::new(header.cart_code);
 
-        Self {
-            filename,
+        Ok(Self {
+            name: name.map(str::to_string),
             rom_size,
             rom_data,
+            ram,
             header,
+            mbc,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None => self.header.title.trim_end_matches('\0'),
         }
     }
 
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The ROM bank mapped at `address`, or 0 outside of ROM.
+    pub fn rom_bank(&self, address: u16) -> u16 {
+        let bank = match address {
+            0x0000..=0x3FFF if self.mbc.advanced_mode => (self.mbc.bank2 as usize) << 5,
+            0x4000..=0x7FFF => ((self.mbc.bank2 as usize) << 5) | self.mbc.rom_bank as usize,
+            _ => 0,
+        };
+
+        (bank % (self.rom_data.len() / 0x4000)) as u16
+    }
+
     pub fn read(&self, address: u16) -> u8 {
-        self.rom_data[address as usize]
+        match address {
+            0x0000..=0x7FFF => {
+                let offset = address & 0x3FFF;
+                self.rom_data[self.rom_bank(address) as usize * 0x4000 + offset as usize]
+            }
+            0xA000..=0xBFFF => match self.ram_address(address) {
+                Some(index) => self.ram[index],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
-        self.rom_data[address as usize] = value;
+        match address {
+            0xA000..=0xBFFF => {
+                if let Some(index) = self.ram_address(address) {
+                    self.ram[index] = value;
+                }
+            }
+            _ if !self.mbc.enabled => {}
+            0x0000..=0x1FFF => self.mbc.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.mbc.rom_bank = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.mbc.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.mbc.advanced_mode = value & 1 != 0,
+            _ => {}
+        }
+    }
+
+    /// Puts the mapper back to its power-on state. RAM contents survive, like a
+    /// battery-backed save across a power cycle.
+    pub fn reset(&mut self) {
+        self.mbc = Mbc1::new(self.header.cart_code);
+    }
+
+    fn ram_address(&self, address: u16) -> Option<usize> {
+        if !self.mbc.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let bank = if self.mbc.advanced_mode {
+            self.mbc.bank2 as usize
+        } else {
+            0
+        };
+        let index = bank * 0x2000 + (address - 0xA000) as usize;
+
+        Some(index % self.ram.len())
     }
 }
 
@@ -413,6 +604,19 @@ impl Cartridge {
 mod tests {
     use super::*;
 
+    /// A blank 32 KB ROM only image with a valid header.
+    fn blank_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        fix_checksum(&mut rom);
+        rom
+    }
+
+    fn fix_checksum(rom: &mut [u8]) {
+        rom[0x14D] = rom[0x134..=0x14C]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1));
+    }
+
     #[test]
     fn test_rom_size() {
         let data = [
@@ -468,16 +672,193 @@ mod tests {
 
     #[test]
     fn test_read() {
-        let cartridge = Cartridge::from("test_roms/01-special.test");
+        let cartridge = Cartridge::from("test_roms/01-special.test").unwrap();
 
         assert_eq!(cartridge.read(0x101), 195)
     }
 
     #[test]
     fn test_write() {
-        let mut cartridge = Cartridge::from("test_roms/01-special.test");
-        assert_ne!(cartridge.read(0x7999), 255);
+        let mut cartridge = Cartridge::from("test_roms/01-special.test").unwrap();
+        let rom = cartridge.read(0x7999);
         cartridge.write(0x7999, 255);
-        assert_eq!(cartridge.read(0x7999), 255);
+        assert_eq!(cartridge.read(0x7999), rom);
+    }
+
+    /// An MBC1 image with 8 KB of RAM whose banks each start with their own number.
+    fn mbc1_rom(rom_size_code: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000 << rom_size_code];
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        rom[0x147] = 0x03;
+        rom[0x148] = rom_size_code;
+        rom[0x149] = 0x02;
+        fix_checksum(&mut rom);
+        rom
+    }
+
+    #[test]
+    fn test_mbc1_switches_rom_banks() {
+        let mut cartridge = Cartridge::from_bytes(mbc1_rom(2), None).unwrap();
+        assert_eq!(cartridge.read(0x4000), 1);
+
+        cartridge.write(0x2000, 3);
+        assert_eq!(cartridge.read(0x4000), 3);
+        assert_eq!(cartridge.read(0x0000), 0);
+
+        cartridge.write(0x2000, 0);
+        assert_eq!(cartridge.read(0x4000), 1);
+    }
+
+    #[test]
+    fn test_reset_restores_the_power_on_banks() {
+        let mut cartridge = Cartridge::from_bytes(mbc1_rom(6), None).unwrap();
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA000, 0x42);
+        cartridge.write(0x2000, 5);
+        cartridge.write(0x4000, 1);
+        cartridge.write(0x6000, 1);
+
+        cartridge.reset();
+        assert_eq!(cartridge.read(0x0000), 0);
+        assert_eq!(cartridge.read(0x4000), 1);
+        assert_eq!(cartridge.read(0xA000), 0xFF);
+
+        cartridge.write(0x0000, 0x0A);
+        assert_eq!(cartridge.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_mbc1_bank2_selects_upper_rom_banks() {
+        let mut cartridge = Cartridge::from_bytes(mbc1_rom(6), None).unwrap();
+        cartridge.write(0x2000, 2);
+        cartridge.write(0x4000, 1);
+        assert_eq!(cartridge.read(0x4000), 34);
+        assert_eq!(cartridge.read(0x0000), 0);
+
+        cartridge.write(0x6000, 1);
+        assert_eq!(cartridge.read(0x0000), 32);
+    }
+
+    #[test]
+    fn test_rom_bank_reports_the_mapped_bank() {
+        let mut cartridge = Cartridge::from_bytes(mbc1_rom(2), None).unwrap();
+        cartridge.write(0x2000, 3);
+
+        assert_eq!(cartridge.rom_bank(0x0150), 0);
+        assert_eq!(cartridge.rom_bank(0x4150), 3);
+        assert_eq!(cartridge.rom_bank(0xC000), 0);
+
+        // Bank numbers wrap around the banks the ROM actually has.
+        cartridge.write(0x2000, 9);
+        assert_eq!(cartridge.rom_bank(0x4150), 1);
+    }
+
+    #[test]
+    fn test_mbc1_ram_needs_enabling() {
+        let mut cartridge = Cartridge::from_bytes(mbc1_rom(0), None).unwrap();
+        cartridge.write(0xA000, 0x42);
+        assert_eq!(cartridge.read(0xA000), 0xFF);
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA000, 0x42);
+        assert_eq!(cartridge.read(0xA000), 0x42);
+
+        cartridge.write(0x0000, 0x00);
+        assert_eq!(cartridge.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_missing_file_is_io_error() {
+        let result = Cartridge::from("test_roms/does-not-exist.gb");
+        assert!(matches!(result, Err(CartridgeError::Io(_))));
+    }
+
+    #[test]
+    fn test_short_rom_is_too_small() {
+        let result = Cartridge::from_bytes(&[0; 0x14F][..], None);
+        assert!(matches!(result, Err(CartridgeError::TooSmall(0x14F))));
+    }
+
+    #[test]
+    fn test_bad_header_checksum() {
+        let mut rom = blank_rom();
+        rom[0x14D] ^= 0xFF;
+
+        let result = Cartridge::from_bytes(rom, None);
+        assert!(matches!(
+            result,
+            Err(CartridgeError::BadHeaderChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let mut rom = blank_rom();
+        rom[0x147] = 0x13;
+        fix_checksum(&mut rom);
+
+        let result = Cartridge::from_bytes(rom, None);
+        assert!(matches!(
+            result,
+            Err(CartridgeError::UnsupportedMapper(0x13))
+        ));
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let mut rom = blank_rom();
+        rom[0x148] = 1;
+        fix_checksum(&mut rom);
+
+        let result = Cartridge::from_bytes(rom, None);
+        assert!(matches!(
+            result,
+            Err(CartridgeError::SizeMismatch {
+                rom_size_code: 1,
+                actual: 0x8000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_rom_size_code() {
+        let mut rom = blank_rom();
+        rom[0x148] = 9;
+        fix_checksum(&mut rom);
+
+        let result = Cartridge::from_bytes(rom, None);
+        assert!(matches!(
+            result,
+            Err(CartridgeError::SizeMismatch {
+                rom_size_code: 9,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_valid_rom_loads() {
+        assert!(Cartridge::from_bytes(blank_rom(), None).is_ok());
+    }
+
+    #[test]
+    fn test_name_falls_back_to_header_title() {
+        let mut rom = blank_rom();
+        rom[0x134..0x138].copy_from_slice(b"TEST");
+        fix_checksum(&mut rom);
+
+        let cartridge = Cartridge::from_bytes(&rom[..], None).unwrap();
+        assert_eq!(cartridge.name(), "TEST");
+
+        let cartridge = Cartridge::from_bytes(rom, Some("generated")).unwrap();
+        assert_eq!(cartridge.name(), "generated");
+    }
+
+    #[test]
+    fn test_path_loader_names_the_file() {
+        let cartridge = Cartridge::from("test_roms/dmg-acid2.test").unwrap();
+        assert_eq!(cartridge.name(), "test_roms/dmg-acid2.test");
     }
 }
@@ -1,260 +1,224 @@
-use crate::bus::Bus;
 use crate::cpu::{conditions::ConditionType, register::RegisterType};
 use crate::interrupts;
 use crate::interrupts::Interrupt;
-use crate::tpu::Timer;
+use crate::memory::Memory;
+use crate::model::Model;
+use crate::trace::Tracer;
 use instructions::Instruction;
 use register::Register;
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
 
 mod actions;
 mod addresses;
 mod conditions;
+pub mod disassembler;
 mod instructions;
 mod register;
 
+/// A snapshot of the CPU registers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
 pub struct CPU {
     register: Register,
     fetch_data: u16,
     mem_dest: u16,
     dest_is_mem: bool,
     current_op_code: u8,
-    instruction: Instruction,
     halted: bool,
+    halt_bug: bool,
+    stopped: bool,
+    locked: bool,
     stepping: bool,
     master_enabled: bool,
     enabling_ime: bool,
-    pub int_flags: u8,
-    ie_register: u8,
-    cycle: u32,
-    log: String,
-    debug_message: String,
+    tracer: Option<Tracer>,
 }
 
 impl CPU {
-    pub fn new() -> Self {
+    /// The state `model`'s boot ROM hands over to the cartridge with, at 0x0100.
+    pub fn new(model: Model) -> Self {
+        let registers = model.registers();
+        CPU::with_register(Register {
+            a: registers.a as u16,
+            f: registers.f as u16,
+            b: registers.b as u16,
+            c: registers.c as u16,
+            d: registers.d as u16,
+            e: registers.e as u16,
+            h: registers.h as u16,
+            l: registers.l as u16,
+            sp: registers.sp,
+            pc: registers.pc,
+        })
+    }
+
+    /// Cleared registers at 0x0000, for running a boot ROM.
+    pub fn power_on() -> Self {
+        CPU::with_register(Register {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+        })
+    }
+
+    fn with_register(register: Register) -> Self {
         Self {
-            register: Register {
-                a: 0x01,
-                f: 0xB0,
-                b: 0,
-                c: 0x13,
-                d: 0,
-                e: 0xD8,
-                h: 0x01,
-                l: 0x4D,
-                sp: 0xFFFE,
-                pc: 0x0100,
-            },
+            register,
             fetch_data: 0,
             mem_dest: 0,
             dest_is_mem: false,
             current_op_code: 0,
-            instruction: Instruction::from(0),
             halted: false,
+            halt_bug: false,
+            stopped: false,
+            locked: false,
             stepping: false,
             master_enabled: false,
             enabling_ime: false,
-            int_flags: 0,
-            ie_register: 0,
-            cycle: 0,
-            log: String::new(),
-            debug_message: String::new(),
+            tracer: None,
         }
     }
 
     pub fn test() -> Self {
-        Self {
-            register: Register {
-                a: 0x01,
-                f: 0xB0,
-                b: 0,
-                c: 0x13,
-                d: 0,
-                e: 0xD8,
-                h: 0x01,
-                l: 0x4D,
-                sp: 0xFFFE,
-                pc: 0x0100,
-            },
-            fetch_data: 0,
-            mem_dest: 0,
-            dest_is_mem: false,
-            current_op_code: 0,
-            instruction: Instruction::from(0),
-            halted: false,
-            stepping: false,
-            master_enabled: false,
-            enabling_ime: false,
-            int_flags: 0,
-            ie_register: 0,
-            cycle: 0,
-            log: String::new(),
-            debug_message: String::new(),
-        }
+        CPU::new(Model::DMG)
     }
 
-    pub fn step(&mut self) {
-        self.log();
-        self.debug_update();
-        self.debug_print();
-        // self.log_to_stdout();
+    pub fn step(&mut self, bus: &mut dyn Memory) {
+        if self.locked {
+            // Only a reset gets the CPU out of an illegal opcode, interrupts included.
+            bus.tick(1);
+            return;
+        }
+
+        if self.stopped {
+            // Nothing runs until a selected joypad line goes low. Time still passes an
+            // M-cycle per step, as when locked, so hosts counting cycles don't spin.
+            if bus.peek(0xFF00) & 0x0F == 0x0F {
+                bus.tick(1);
+                return;
+            }
+            self.stopped = false;
+        }
+
+        // EI takes effect once the instruction after it has run, unless that was DI.
+        let ime_scheduled = self.enabling_ime;
+
         if !self.halted {
-            let instruction = self.fetch_instruction();
-            instruction.execute(self);
+            if let Some(mut tracer) = self.tracer.take() {
+                tracer.trace(self.registers(), bus);
+                self.tracer = Some(tracer);
+            }
 
-            self.cycle += 1;
+            let instruction = self.fetch_instruction(bus);
+            instruction.execute(self, bus);
         } else {
-            Timer::get().emu_cycles(1, self);
+            bus.tick(1);
 
-            if self.int_flags != 0 {
+            if bus.interrupts().pending() != 0 {
                 self.halted = false;
             }
         }
 
-        if self.master_enabled {
-            self.handle_interrupts();
+        if ime_scheduled && self.enabling_ime {
             self.enabling_ime = false;
+            self.master_enabled = true;
         }
 
-        if self.enabling_ime {
-            self.master_enabled = true;
+        if self.master_enabled && bus.interrupts().pending() != 0 {
+            self.handle_interrupts(bus);
         }
     }
 
-    pub fn request_interrupt(&mut self, it_timer: u8) {
-        todo!()
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
     }
 
-    fn log_to_stdout(&self) {
-        let z: &str;
-        let n: &str;
-        let h: &str;
-        let c: &str;
-        if self.register.z_flag() {
-            z = "Z";
-        } else {
-            z = "-";
-        }
-        if self.register.n_flag() {
-            n = "N";
-        } else {
-            n = "-";
-        }
-        if self.register.h_flag() {
-            h = "H";
-        } else {
-            h = "-";
-        }
-        if self.register.c_flag() {
-            c = "C";
-        } else {
-            c = "-";
-        }
-
-        println!(
-            "{:#08x}: {:#04x} {: <4} | PC: {:#06x} | DATA {:#06x} | a: {:#04x}; bc: {:#06x}; de: {:#06x}; sp: {:#06x}; hl: {:#06x} | {}{}{}{}",
-            self.cycle,
-            self.current_op_code,
-            self.instruction.action.to_string(),
-            self.register.pc,
-            self.fetch_data,
-            self.register.a,
-            self.read_register(&RegisterType::BC),
-            self.read_register(&RegisterType::DE),
-            self.register.sp,
-            self.read_register(&RegisterType::HL),
-            z,
-            n,
-            h,
-            c
-        );
+    /// The address and value of the illegal opcode the CPU locked up on, if it has.
+    pub fn illegal_opcode(&self) -> Option<(u16, u8)> {
+        self.locked
+            .then(|| (self.register.pc.wrapping_sub(1), self.current_op_code))
     }
 
-    fn log(&mut self) {
-        let data_pc: u16 = Bus::get().read(self.register.pc, &self);
-        let data_pc_1: u16 = Bus::get().read(self.register.pc + 1, &self);
-        let data_pc_2: u16 = Bus::get().read(self.register.pc + 2, &self);
-        let data_pc_3: u16 = Bus::get().read(self.register.pc + 3, &self);
-
-        let log = format!(
-            "A:{:#04X} F:{:#04X} B:{:#04X} C:{:#04X} D:{:#04X} E:{:#04X} H:{:#04X} L:{:#04X} SP:{:#06X} PC:{:#06X} PCMEM:{:#04X},{:#04X},{:#04X},{:#04X}\n",
-            self.register.a,
-            self.register.f,
-            self.register.b,
-            self.register.c,
-            self.register.d,
-            self.register.e,
-            self.register.h,
-            self.register.l,
-            self.register.sp,
-            self.register.pc,
-            data_pc,
-            data_pc_1,
-            data_pc_2,
-            data_pc_3,
-        ).replace("0x", "");
-        self.log.push_str(&log);
-    }
-
-    fn save_log(&self) {
-        let file = match OpenOptions::new().append(true).open("log.txt") {
-            Ok(file) => File::create("log.txt").expect("Could not create log.txt"),
-            Err(_) => File::create("log.txt").expect("Could not create log.txt"),
-        };
-        let mut buffer = BufWriter::new(file);
-        buffer
-            .write(self.log.as_ref())
-            .expect("Could not write to log.txt");
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
     }
 
-    fn debug_update(&mut self) {
-        if Bus::get().read(0xFF02, &self) as u8 == 0x81 {
-            self.debug_message
-                .push(Bus::get().read(0xFF01, &self) as u8 as char);
-            Bus::get().write(0xFF02, 0x00, self);
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.register.a as u8,
+            f: self.register.f as u8,
+            b: self.register.b as u8,
+            c: self.register.c as u8,
+            d: self.register.d as u8,
+            e: self.register.e as u8,
+            h: self.register.h as u8,
+            l: self.register.l as u8,
+            sp: self.register.sp,
+            pc: self.register.pc,
         }
     }
 
-    fn debug_print(&self) {
-        if !self.debug_message.is_empty() {
-            println!("DBG: {}", self.debug_message);
-        }
-    }
+    /// Dispatch takes five M-cycles: two idle, two pushing PC and one jumping. The
+    /// vector is only picked after the high byte is pushed, so a push that lands on IE
+    /// can cancel the interrupt, in which case the CPU jumps to 0x0000.
+    fn handle_interrupts(&mut self, bus: &mut dyn Memory) {
+        self.master_enabled = false;
+        self.halted = false;
 
-    fn handle_interrupts(&mut self) {
-        if self.interrupt_check(0x40, Interrupt::VBlank) {
-        } else if self.interrupt_check(0x48, Interrupt::LCDStat) {
-        } else if self.interrupt_check(0x50, Interrupt::Timer) {
-        } else if self.interrupt_check(0x58, Interrupt::Serial) {
-        } else if self.interrupt_check(0x60, Interrupt::JoyPad) {
-        }
-    }
+        // An interrupt taken straight after the HALT bug returns to the HALT itself.
+        let pc = if self.halt_bug {
+            self.halt_bug = false;
+            self.register.pc.wrapping_sub(1)
+        } else {
+            self.register.pc
+        };
 
-    fn interrupt_check(&mut self, address: u16, interrupt: Interrupt) -> bool {
-        let int_num = interrupts::fetch_interrupt_num(interrupt);
+        bus.tick(2);
+        self.stack_push((pc >> 8) as u8, bus);
+        bus.tick(1);
 
-        if self.int_flags & int_num != 0 && self.ie_register & int_num != 0 {
-            self.handle_interrupt(address);
-            self.int_flags &= !int_num;
-            self.halted = false;
-            self.master_enabled = false;
+        let pending = bus.interrupts().pending();
+        self.stack_push((pc & 0xFF) as u8, bus);
+        bus.tick(1);
 
-            true
-        } else {
-            false
-        }
+        self.register.pc = match interrupt_vector(pending) {
+            Some((address, interrupt)) => {
+                bus.interrupts().acknowledge(interrupt);
+                address
+            }
+            None => 0x0000,
+        };
+        bus.tick(1);
     }
 
-    fn handle_interrupt(&mut self, address: u16) {
-        self.stack_push16(self.register.pc);
-        self.register.pc = address;
-    }
+    fn fetch_instruction(&mut self, bus: &mut dyn Memory) -> Instruction {
+        self.current_op_code = bus.read(self.register.pc);
+        bus.tick(1);
+
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.register.pc = self.register.pc.wrapping_add(1);
+        }
 
-    fn fetch_instruction(&mut self) -> Instruction {
-        self.current_op_code = Bus::get().read(self.register.pc, &self) as u8;
-        self.register.pc += 1;
         Instruction::from(self.current_op_code)
     }
 
@@ -278,36 +242,6 @@ impl CPU {
         }
     }
 
-    fn read_register8(&self, register: &RegisterType) -> u8 {
-        match register {
-            RegisterType::A => self.register.a as u8,
-            RegisterType::F => self.register.f as u8,
-            RegisterType::B => self.register.b as u8,
-            RegisterType::C => self.register.c as u8,
-            RegisterType::D => self.register.d as u8,
-            RegisterType::E => self.register.e as u8,
-            RegisterType::H => self.register.h as u8,
-            RegisterType::L => self.register.l as u8,
-            RegisterType::HL => Bus::get().read(self.read_register(register), &self) as u8,
-            _ => panic!("{:?} is not a valid 8bit register", register),
-        }
-    }
-
-    fn set_register8(&mut self, register: &RegisterType, value: u8) {
-        match register {
-            RegisterType::A => self.register.a = (value & 0xFF) as u16,
-            RegisterType::F => self.register.f = (value & 0xFF) as u16,
-            RegisterType::B => self.register.b = (value & 0xFF) as u16,
-            RegisterType::C => self.register.c = (value & 0xFF) as u16,
-            RegisterType::D => self.register.d = (value & 0xFF) as u16,
-            RegisterType::E => self.register.e = (value & 0xFF) as u16,
-            RegisterType::H => self.register.h = (value & 0xFF) as u16,
-            RegisterType::L => self.register.l = (value & 0xFF) as u16,
-            RegisterType::HL => Bus::get().write(self.read_register(register), value, self),
-            _ => panic!("{:?} is not a valid 8bit register", register),
-        }
-    }
-
     fn set_register(&mut self, register_type: &RegisterType, value: u16) {
         match register_type {
             RegisterType::NONE => {}
@@ -352,71 +286,85 @@ impl CPU {
         }
     }
 
-    fn stack_push(&mut self, data: u8) {
+    fn stack_push(&mut self, data: u8, bus: &mut dyn Memory) {
         self.register.sp = self.register.sp.wrapping_sub(1);
-        Bus::get().write(self.register.sp, data, self);
+        bus.write(self.register.sp, data);
     }
 
-    fn stack_push16(&mut self, data: u16) {
-        self.stack_push(((data >> 8) & 0xFF) as u8);
-        self.stack_push((data & 0xFF) as u8);
+    fn stack_push16(&mut self, data: u16, bus: &mut dyn Memory) {
+        self.stack_push(((data >> 8) & 0xFF) as u8, bus);
+        self.stack_push((data & 0xFF) as u8, bus);
     }
 
-    fn stack_pop(&mut self) -> u16 {
-        let data = Bus::get().read(self.register.sp, &self);
-        self.register.sp += 1;
+    fn stack_pop(&mut self, bus: &mut dyn Memory) -> u16 {
+        let data = bus.read(self.register.sp) as u16;
+        self.register.sp = self.register.sp.wrapping_add(1);
 
         data
     }
 
-    fn stack_pop16(&mut self) -> u16 {
-        let lo: u16 = self.stack_pop();
-        let hi: u16 = self.stack_pop();
+    fn stack_pop16(&mut self, bus: &mut dyn Memory) -> u16 {
+        let lo: u16 = self.stack_pop(bus);
+        let hi: u16 = self.stack_pop(bus);
 
         (hi << 8) | lo
     }
 
-    fn go_to(&mut self, address: u16, push_pc: bool, instruction: &Instruction) {
+    fn go_to(
+        &mut self,
+        address: u16,
+        push_pc: bool,
+        instruction: &Instruction,
+        bus: &mut dyn Memory,
+    ) {
         if instruction.condition.check(self) {
+            bus.tick(1);
+
             if push_pc {
-                Timer::get().emu_cycles(2, self);
-                self.stack_push16(self.register.pc)
+                self.stack_push((self.register.pc >> 8) as u8, bus);
+                bus.tick(1);
+                self.stack_push(self.register.pc as u8, bus);
+                bus.tick(1);
             }
 
             self.register.pc = address;
-            Timer::get().emu_cycles(1, self);
         }
     }
 
-    fn return_from_procedure(&mut self, instruction: &Instruction) {
-        match self.instruction.condition {
+    fn return_from_procedure(&mut self, instruction: &Instruction, bus: &mut dyn Memory) {
+        // Checking the condition takes a cycle of its own.
+        match instruction.condition {
             ConditionType::NONE => {}
-            _ => Timer::get().emu_cycles(1, self),
+            _ => bus.tick(1),
         }
 
         if instruction.condition.check(self) {
-            let lo = self.stack_pop();
-            Timer::get().emu_cycles(1, self);
-            let hi = self.stack_pop();
-            Timer::get().emu_cycles(1, self);
+            let lo = self.stack_pop(bus);
+            bus.tick(1);
+            let hi = self.stack_pop(bus);
+            bus.tick(1);
             self.register.pc = (hi << 8) | lo;
-            Timer::get().emu_cycles(1, self);
+            bus.tick(1);
         }
     }
 }
 
-impl Drop for CPU {
-    fn drop(&mut self) {
-        self.save_log();
-    }
+/// The highest priority pending interrupt and its vector.
+fn interrupt_vector(pending: u8) -> Option<(u16, u8)> {
+    [
+        (0x40, Interrupt::VBlank),
+        (0x48, Interrupt::LCDStat),
+        (0x50, Interrupt::Timer),
+        (0x58, Interrupt::Serial),
+        (0x60, Interrupt::JoyPad),
+    ]
+    .into_iter()
+    .map(|(address, interrupt)| (address, interrupts::fetch_interrupt_num(interrupt)))
+    .find(|&(_, interrupt)| pending & interrupt != 0)
 }
 
 fn bit(a: u8, n: u8) -> bool {
-    if (a & (1 << n)) != 0 {
-        true
-    } else {
-        false
-    }
+    (a & (1 << n)) != 0
 }
 
 fn bit_set(a: u8, n: u8, on: bool) -> u8 {
@@ -434,10 +382,756 @@ fn reverse(num: u16) -> u16 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::interrupts::Interrupts;
 
     #[test]
     fn test_reverse() {
         assert_eq!(reverse(0x00EE), 0xEE00);
         assert_eq!(reverse(0xEE00), 0x00EE)
     }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Access {
+        Read(u16),
+        Write(u16, u8),
+    }
+
+    /// 64 KiB of plain RAM that records every access with the M-cycle it happened on.
+    struct FlatMemory {
+        data: Vec<u8>,
+        speed_switch: bool,
+        interrupts: Interrupts,
+        cycles: u64,
+        accesses: Vec<(u64, Access)>,
+    }
+
+    impl FlatMemory {
+        fn with_program(program: &[u8]) -> Self {
+            let mut data = vec![0; 0x10000];
+            data[0x0100..0x0100 + program.len()].copy_from_slice(program);
+
+            Self {
+                data,
+                speed_switch: false,
+                interrupts: Interrupts::new(),
+                cycles: 0,
+                accesses: Vec::new(),
+            }
+        }
+
+        fn writes(&self) -> Vec<&(u64, Access)> {
+            self.accesses
+                .iter()
+                .filter(|(_, access)| matches!(access, Access::Write(..)))
+                .collect()
+        }
+    }
+
+    impl Memory for FlatMemory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.accesses.push((self.cycles, Access::Read(address)));
+            self.peek(address)
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.accesses
+                .push((self.cycles, Access::Write(address, value)));
+
+            match address {
+                0xFF0F => self.interrupts.write(value),
+                0xFFFF => self.interrupts.write_enable(value),
+                _ => self.data[address as usize] = value,
+            }
+        }
+
+        fn peek(&self, address: u16) -> u8 {
+            match address {
+                0xFF0F => self.interrupts.read(),
+                0xFFFF => self.interrupts.read_enable(),
+                _ => self.data[address as usize],
+            }
+        }
+
+        fn tick(&mut self, cycles: u8) {
+            self.cycles += cycles as u64;
+        }
+
+        fn stop(&mut self) -> bool {
+            self.speed_switch
+        }
+
+        fn interrupts(&mut self) -> &mut Interrupts {
+            &mut self.interrupts
+        }
+    }
+
+    #[test]
+    fn test_operands_are_read_in_order() {
+        let mut memory = FlatMemory::with_program(&[0x01, 0x34, 0x12]);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+
+        let reads: Vec<&Access> = memory.accesses.iter().map(|(_, access)| access).collect();
+        assert_eq!(
+            reads,
+            vec![
+                &Access::Read(0x0100),
+                &Access::Read(0x0101),
+                &Access::Read(0x0102)
+            ]
+        );
+        assert_eq!(cpu.read_register(&RegisterType::BC), 0x1234);
+        assert_eq!(cpu.register.pc, 0x0103);
+    }
+
+    #[test]
+    fn test_ld_hl_a_writes_through_memory() {
+        let mut memory = FlatMemory::with_program(&[0x77]);
+        let mut cpu = CPU::test();
+        cpu.set_register(&RegisterType::HL, 0xC000);
+
+        cpu.step(&mut memory);
+
+        assert_eq!(memory.writes(), vec![&(1, Access::Write(0xC000, 0x01))]);
+        assert_eq!(memory.data[0xC000], 0x01);
+    }
+
+    #[test]
+    fn test_push_writes_high_byte_first_on_separate_cycles() {
+        let mut memory = FlatMemory::with_program(&[0xC5]);
+        let mut cpu = CPU::test();
+        cpu.set_register(&RegisterType::BC, 0xBEEF);
+
+        cpu.step(&mut memory);
+
+        let writes = memory.writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].1, Access::Write(0xFFFD, 0xBE));
+        assert_eq!(writes[1].1, Access::Write(0xFFFC, 0xEF));
+        assert!(writes[0].0 < writes[1].0);
+        assert_eq!(cpu.register.sp, 0xFFFC);
+    }
+
+    /// The B, C, D, E, H, L, (HL), A operand order of the CB table, with (HL) at 0xC000.
+    fn cb_operand(cpu: &CPU, memory: &FlatMemory, index: u8) -> u8 {
+        match index {
+            0 => cpu.register.b as u8,
+            1 => cpu.register.c as u8,
+            2 => cpu.register.d as u8,
+            3 => cpu.register.e as u8,
+            4 => cpu.register.h as u8,
+            5 => cpu.register.l as u8,
+            6 => memory.data[0xC000],
+            _ => cpu.register.a as u8,
+        }
+    }
+
+    /// What CB `op` turns `value` and `f` into, worked out without the CPU.
+    fn cb_expected(op: u8, value: u8, f: u8) -> (u8, u8) {
+        let bit = (op >> 3) & 7;
+        let carry = (f >> 4) & 1;
+
+        let (result, carry) = match op >> 6 {
+            0 => match bit {
+                0 => (value.rotate_left(1), value >> 7),
+                1 => (value.rotate_right(1), value & 1),
+                2 => ((value << 1) | carry, value >> 7),
+                3 => ((value >> 1) | (carry << 7), value & 1),
+                4 => (value << 1, value >> 7),
+                5 => (((value as i8) >> 1) as u8, value & 1),
+                6 => (((value & 0x0F) << 4) | ((value & 0xF0) >> 4), 0),
+                _ => (value >> 1, value & 1),
+            },
+            1 => {
+                let z = if value & (1 << bit) == 0 { 0x80 } else { 0 };
+                return (value, z | 0x20 | (f & 0x10));
+            }
+            2 => return (value & !(1 << bit), f),
+            _ => return (value | (1 << bit), f),
+        };
+
+        let z = if result == 0 { 0x80 } else { 0 };
+        (result, z | (carry << 4))
+    }
+
+    #[test]
+    fn test_every_cb_opcode() {
+        for op in 0..=0xFF_u8 {
+            for value in [0x00, 0x01, 0x80, 0x85, 0x5A, 0xF0, 0xFF] {
+                for f in [0x00, 0x10, 0x60, 0xF0] {
+                    let mut memory = FlatMemory::with_program(&[0xCB, op]);
+                    let mut cpu = CPU::test();
+                    cpu.register.b = 0x11;
+                    cpu.register.c = 0x22;
+                    cpu.register.d = 0x33;
+                    cpu.register.e = 0x44;
+                    cpu.register.a = 0x77;
+                    cpu.set_register(&RegisterType::HL, 0xC000);
+                    cpu.register.f = f;
+
+                    let index = op & 7;
+                    match index {
+                        0 => cpu.register.b = value as u16,
+                        1 => cpu.register.c = value as u16,
+                        2 => cpu.register.d = value as u16,
+                        3 => cpu.register.e = value as u16,
+                        4 => cpu.register.h = value as u16,
+                        5 => cpu.register.l = value as u16,
+                        6 => memory.data[0xC000] = value,
+                        _ => cpu.register.a = value as u16,
+                    }
+                    let before: Vec<u8> = (0..8).map(|i| cb_operand(&cpu, &memory, i)).collect();
+
+                    cpu.step(&mut memory);
+
+                    let (result, flags) = cb_expected(op, value, f as u8);
+                    let context = format!("CB {:02X} on {:02X} with F={:02X}", op, value, f);
+                    for i in 0..8 {
+                        let expected = if i == index {
+                            result
+                        } else {
+                            before[i as usize]
+                        };
+                        assert_eq!(cb_operand(&cpu, &memory, i), expected, "{}", context);
+                    }
+                    assert_eq!(cpu.register.f as u8, flags, "{}", context);
+                    assert_eq!(cpu.register.pc, 0x0102, "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cb_cycle_counts() {
+        // Register forms take 2 M-cycles, BIT n,(HL) 3, and the other (HL) forms 4.
+        for (op, cycles) in [
+            (0x00, 2),
+            (0x47, 2),
+            (0x46, 3),
+            (0x7E, 3),
+            (0x06, 4),
+            (0x86, 4),
+            (0xFE, 4),
+        ] {
+            let mut memory = FlatMemory::with_program(&[0xCB, op]);
+            let mut cpu = CPU::test();
+            cpu.set_register(&RegisterType::HL, 0xC000);
+
+            cpu.step(&mut memory);
+
+            assert_eq!(memory.cycles, cycles, "CB {:02X}", op);
+        }
+    }
+
+    #[test]
+    fn test_cb_hl_reads_then_writes_on_the_next_cycle() {
+        // SET 0, (HL)
+        let mut memory = FlatMemory::with_program(&[0xCB, 0xC6]);
+        let mut cpu = CPU::test();
+        cpu.set_register(&RegisterType::HL, 0xC000);
+
+        cpu.step(&mut memory);
+
+        assert_eq!(
+            memory.accesses,
+            vec![
+                (0, Access::Read(0x0100)),
+                (1, Access::Read(0x0101)),
+                (2, Access::Read(0xC000)),
+                (3, Access::Write(0xC000, 0x01)),
+            ]
+        );
+    }
+
+    /// M-cycles per opcode with conditions not met, 0 for the ones without a fixed time.
+    #[rustfmt::skip]
+    const CYCLES: [u8; 256] = [
+        1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1,
+        0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+        2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 2, 2, 2, 2, 2, 0, 2, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+        2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4,
+        2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4,
+        3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4,
+        3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4,
+    ];
+
+    #[test]
+    fn test_instruction_cycle_counts() {
+        let mut wrong = Vec::new();
+
+        for op in 0..=0xFF_u8 {
+            if CYCLES[op as usize] == 0 {
+                continue;
+            }
+
+            for taken in [false, true] {
+                let conditional = matches!(op & 0xE7, 0x20 | 0xC0 | 0xC2 | 0xC4);
+                if taken && !conditional {
+                    continue;
+                }
+
+                let mut memory = FlatMemory::with_program(&[op, 0x00, 0x00]);
+                let mut cpu = CPU::test();
+                cpu.set_register(&RegisterType::HL, 0xC000);
+                cpu.set_register(&RegisterType::SP, 0xD000);
+                // Z and C set fail NZ and NC, clear fail Z and C.
+                let negated = op & 0x08 == 0;
+                cpu.register.f = if negated == taken { 0x00 } else { 0x90 };
+
+                cpu.step(&mut memory);
+
+                let extra = match (taken, op & 0xC7) {
+                    (false, _) => 0,
+                    (true, 0x00) | (true, 0xC2) => 1,
+                    _ => 3,
+                };
+                let expected = CYCLES[op as usize] + extra;
+                if memory.cycles != expected as u64 {
+                    wrong.push(format!(
+                        "{:02X}{}: {} not {}",
+                        op,
+                        if taken { " taken" } else { "" },
+                        memory.cycles,
+                        expected
+                    ));
+                }
+            }
+        }
+
+        assert!(wrong.is_empty(), "{:#?}", wrong);
+    }
+
+    #[test]
+    fn test_memory_accesses_land_on_their_m_cycle() {
+        use Access::{Read, Write};
+
+        type Accesses = &'static [(u64, Access)];
+        let cases: [(&[u8], Accesses); 11] = [
+            // LD (0xC000), SP
+            (
+                &[0x08, 0x00, 0xC0],
+                &[
+                    (1, Read(0x0101)),
+                    (2, Read(0x0102)),
+                    (3, Write(0xC000, 0x00)),
+                    (4, Write(0xC001, 0xD0)),
+                ],
+            ),
+            // LD (HL), 0x42
+            (
+                &[0x36, 0x42],
+                &[(1, Read(0x0101)), (2, Write(0xC000, 0x42))],
+            ),
+            // INC (HL)
+            (&[0x34], &[(1, Read(0xC000)), (2, Write(0xC000, 0x01))]),
+            // LDH (0x80), A
+            (
+                &[0xE0, 0x80],
+                &[(1, Read(0x0101)), (2, Write(0xFF80, 0x01))],
+            ),
+            // LD A, (0xC000)
+            (
+                &[0xFA, 0x00, 0xC0],
+                &[(1, Read(0x0101)), (2, Read(0x0102)), (3, Read(0xC000))],
+            ),
+            // CALL 0x1234
+            (
+                &[0xCD, 0x34, 0x12],
+                &[
+                    (1, Read(0x0101)),
+                    (2, Read(0x0102)),
+                    (4, Write(0xCFFF, 0x01)),
+                    (5, Write(0xCFFE, 0x03)),
+                ],
+            ),
+            // RST 0x38
+            (
+                &[0xFF],
+                &[(2, Write(0xCFFF, 0x01)), (3, Write(0xCFFE, 0x01))],
+            ),
+            // RET
+            (&[0xC9], &[(1, Read(0xD000)), (2, Read(0xD001))]),
+            // RET NZ, taken
+            (&[0xC0], &[(2, Read(0xD000)), (3, Read(0xD001))]),
+            // POP BC
+            (&[0xC1], &[(1, Read(0xD000)), (2, Read(0xD001))]),
+            // PUSH BC
+            (
+                &[0xC5],
+                &[(2, Write(0xCFFF, 0x00)), (3, Write(0xCFFE, 0x13))],
+            ),
+        ];
+
+        for (program, expected) in cases {
+            let mut memory = FlatMemory::with_program(program);
+            let mut cpu = CPU::test();
+            cpu.set_register(&RegisterType::HL, 0xC000);
+            cpu.set_register(&RegisterType::SP, 0xD000);
+            cpu.register.f = 0;
+
+            cpu.step(&mut memory);
+
+            let mut accesses = vec![(0, Read(0x0100))];
+            accesses.extend(expected.iter().cloned());
+            assert_eq!(memory.accesses, accesses, "{:02X?}", program);
+        }
+    }
+
+    #[test]
+    fn test_pc_wraps_past_0xffff() {
+        // LD BC, d16 with its high operand byte in IE
+        let mut memory = FlatMemory::with_program(&[]);
+        memory.data[0xFFFD] = 0x01;
+        memory.data[0xFFFE] = 0x34;
+        memory.write(0xFFFF, 0x12);
+        let mut cpu = CPU::test();
+        cpu.register.pc = 0xFFFD;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.read_register(&RegisterType::BC), 0x1234);
+        assert_eq!(cpu.register.pc, 0x0000);
+    }
+
+    #[test]
+    fn test_pop_wraps_sp_past_0xffff() {
+        let mut memory = FlatMemory::with_program(&[0xC1]);
+        memory.write(0xFFFF, 0x12);
+        memory.data[0x0000] = 0x34;
+        let mut cpu = CPU::test();
+        cpu.register.sp = 0xFFFF;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.read_register(&RegisterType::BC), 0x3412);
+        assert_eq!(cpu.register.sp, 0x0001);
+    }
+
+    #[test]
+    fn test_call_pushes_return_address() {
+        let mut memory = FlatMemory::with_program(&[0xCD, 0x34, 0x12]);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x1234);
+        assert_eq!(cpu.register.sp, 0xFFFC);
+        assert_eq!(memory.peek(0xFFFD), 0x01);
+        assert_eq!(memory.peek(0xFFFC), 0x03);
+    }
+
+    #[test]
+    fn test_interrupts_dispatch_by_priority() {
+        let mut memory = FlatMemory::with_program(&[0x00]);
+        memory
+            .interrupts
+            .request(interrupts::TIMER | interrupts::JOY_PAD);
+        memory.interrupts.write_enable(0x1F);
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x0050);
+        assert_eq!(memory.peek(0xFF0F), 0xE0 | interrupts::JOY_PAD);
+        assert_eq!(memory.peek(0xFFFD), 0x01);
+        assert_eq!(memory.peek(0xFFFC), 0x01);
+        assert!(!cpu.master_enabled);
+    }
+
+    #[test]
+    fn test_interrupt_dispatch_takes_five_cycles() {
+        let mut memory = FlatMemory::with_program(&[0x00]);
+        memory.interrupts.request(interrupts::V_BLANK);
+        memory.interrupts.write_enable(interrupts::V_BLANK);
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+
+        let before = memory.cycles;
+        cpu.step(&mut memory);
+
+        // One M-cycle for the NOP, then five for the dispatch.
+        assert_eq!(cpu.register.pc, 0x0040);
+        assert_eq!(memory.cycles - before, 6);
+    }
+
+    #[test]
+    fn test_interrupt_enabled_through_ie_fires() {
+        // EI; NOP
+        let mut memory = FlatMemory::with_program(&[0xFB, 0x00]);
+        memory.write(0xFFFF, interrupts::TIMER);
+        memory.write(0xFF0F, interrupts::TIMER);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x0050);
+        assert_eq!(memory.peek(0xFF0F) & 0x1F, 0);
+    }
+
+    #[test]
+    fn test_interrupt_not_enabled_in_ie_is_ignored() {
+        let mut memory = FlatMemory::with_program(&[0xFB, 0x00, 0x00]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        memory.write(0xFF0F, interrupts::TIMER);
+        let mut cpu = CPU::test();
+
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+
+        assert_eq!(cpu.register.pc, 0x0103);
+        assert_eq!(memory.peek(0xFF0F), 0xE0 | interrupts::TIMER);
+    }
+
+    #[test]
+    fn test_halt_waits_for_an_enabled_interrupt() {
+        let mut memory = FlatMemory::with_program(&[0x76, 0x3C]);
+        memory.write(0xFF0F, interrupts::TIMER);
+        let mut cpu = CPU::test();
+
+        for _ in 0..4 {
+            cpu.step(&mut memory);
+        }
+        assert!(cpu.halted);
+        assert_eq!(cpu.register.pc, 0x0101);
+
+        memory.write(0xFFFF, interrupts::TIMER);
+        cpu.step(&mut memory);
+        assert!(!cpu.halted);
+
+        cpu.step(&mut memory);
+        assert_eq!(cpu.register.a, 0x02);
+        assert_eq!(memory.peek(0xFF0F), 0xE0 | interrupts::TIMER);
+    }
+
+    #[test]
+    fn test_halt_with_ime_dispatches_on_wake() {
+        let mut memory = FlatMemory::with_program(&[0x76, 0x3C]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+        assert!(cpu.halted);
+
+        memory.interrupts.request(interrupts::V_BLANK);
+        cpu.step(&mut memory);
+
+        assert!(!cpu.halted);
+        assert_eq!(cpu.register.pc, 0x0040);
+        assert_eq!(memory.peek(0xFFFC), 0x01);
+        assert_eq!(memory.peek(0xFFFD), 0x01);
+    }
+
+    #[test]
+    fn test_halt_bug_reads_the_next_byte_twice() {
+        // HALT; INC A
+        let mut memory = FlatMemory::with_program(&[0x76, 0x3C]);
+        memory.write(0xFFFF, interrupts::SERIAL);
+        memory.write(0xFF0F, interrupts::SERIAL);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        assert!(!cpu.halted);
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.a, 0x03);
+        assert_eq!(cpu.register.pc, 0x0102);
+    }
+
+    #[test]
+    fn test_halt_bug_shifts_operands() {
+        // HALT; LD A, 0x14 is seen as LD A, 0x3E; INC D
+        let mut memory = FlatMemory::with_program(&[0x76, 0x3E, 0x14]);
+        memory.write(0xFFFF, interrupts::JOY_PAD);
+        memory.write(0xFF0F, interrupts::JOY_PAD);
+        let mut cpu = CPU::test();
+
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+
+        assert_eq!(cpu.register.a, 0x3E);
+        assert_eq!(cpu.register.d, 0x01);
+        assert_eq!(cpu.register.pc, 0x0103);
+    }
+
+    #[test]
+    fn test_stop_skips_its_operand_and_waits_for_joypad() {
+        // STOP; INC A
+        let mut memory = FlatMemory::with_program(&[0x10, 0x00, 0x3C]);
+        memory.data[0xFF00] = 0xEF;
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.register.pc, 0x0102);
+
+        let cycles = memory.cycles;
+        cpu.step(&mut memory);
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.register.pc, 0x0102);
+        assert_eq!(memory.cycles, cycles + 1);
+
+        memory.data[0xFF00] = 0xEE;
+        cpu.step(&mut memory);
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.register.a, 0x02);
+    }
+
+    #[test]
+    fn test_stop_with_armed_speed_switch_keeps_running() {
+        let mut memory = FlatMemory::with_program(&[0x10, 0x00, 0x3C]);
+        memory.speed_switch = true;
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.register.pc, 0x0102);
+    }
+
+    #[test]
+    fn test_ei_delays_by_one_instruction() {
+        // EI; INC A; INC A
+        let mut memory = FlatMemory::with_program(&[0xFB, 0x3C, 0x3C]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        memory.write(0xFF0F, interrupts::V_BLANK);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        assert_eq!(cpu.register.pc, 0x0101);
+
+        cpu.step(&mut memory);
+        assert_eq!(cpu.register.a, 0x02);
+        assert_eq!(cpu.register.pc, 0x0040);
+        assert_eq!(memory.peek(0xFFFC), 0x02);
+    }
+
+    #[test]
+    fn test_di_straight_after_ei_keeps_interrupts_off() {
+        // EI; DI; NOP
+        let mut memory = FlatMemory::with_program(&[0xFB, 0xF3, 0x00]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        memory.write(0xFF0F, interrupts::V_BLANK);
+        let mut cpu = CPU::test();
+
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+
+        assert!(!cpu.master_enabled);
+        assert_eq!(cpu.register.pc, 0x0103);
+    }
+
+    #[test]
+    fn test_reti_enables_interrupts_at_once() {
+        // RETI, returning to 0x0100
+        let mut memory = FlatMemory::with_program(&[0xD9]);
+        memory.data[0xFFFC] = 0x00;
+        memory.data[0xFFFD] = 0x01;
+        memory.write(0xFFFF, interrupts::TIMER);
+        memory.write(0xFF0F, interrupts::TIMER);
+        let mut cpu = CPU::test();
+        cpu.register.sp = 0xFFFC;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x0050);
+        assert_eq!(memory.peek(0xFFFD), 0x01);
+        assert_eq!(memory.peek(0xFFFC), 0x00);
+    }
+
+    #[test]
+    fn test_ei_before_halt_returns_to_the_halt() {
+        // EI; HALT
+        let mut memory = FlatMemory::with_program(&[0xFB, 0x76]);
+        memory.write(0xFFFF, interrupts::TIMER);
+        memory.write(0xFF0F, interrupts::TIMER);
+        let mut cpu = CPU::test();
+
+        cpu.step(&mut memory);
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x0050);
+        assert_eq!(memory.peek(0xFFFD), 0x01);
+        assert_eq!(memory.peek(0xFFFC), 0x01);
+    }
+
+    #[test]
+    fn test_dispatch_pushes_then_jumps_on_separate_cycles() {
+        let mut memory = FlatMemory::with_program(&[0x00]);
+        memory.write(0xFFFF, interrupts::LCD_STAT);
+        memory.write(0xFF0F, interrupts::LCD_STAT);
+        memory.accesses.clear();
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+
+        let start = memory.cycles;
+        cpu.step(&mut memory);
+
+        let writes: Vec<(u64, &Access)> = memory
+            .writes()
+            .into_iter()
+            .map(|(cycle, access)| (cycle - start, access))
+            .collect();
+        assert_eq!(
+            writes,
+            vec![
+                (3, &Access::Write(0xFFFD, 0x01)),
+                (4, &Access::Write(0xFFFC, 0x01))
+            ]
+        );
+        assert_eq!(memory.cycles - start, 6);
+        assert_eq!(cpu.register.pc, 0x0048);
+    }
+
+    #[test]
+    fn test_push_onto_ie_cancels_dispatch() {
+        // With SP at 0x0000 the high byte of PC lands on IE and clears it.
+        let mut memory = FlatMemory::with_program(&[0x00]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        memory.write(0xFF0F, interrupts::V_BLANK);
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+        cpu.register.sp = 0x0000;
+        cpu.register.pc = 0x0200;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(cpu.register.pc, 0x0000);
+        assert_eq!(memory.peek(0xFFFF), 0x02);
+        assert_eq!(memory.peek(0xFF0F), 0xE0 | interrupts::V_BLANK);
+    }
+
+    #[test]
+    fn test_push_onto_ie_can_redirect_dispatch() {
+        let mut memory = FlatMemory::with_program(&[0x00]);
+        memory.write(0xFFFF, interrupts::V_BLANK);
+        memory.write(0xFF0F, interrupts::V_BLANK | interrupts::SERIAL);
+        let mut cpu = CPU::test();
+        cpu.master_enabled = true;
+        cpu.register.sp = 0x0000;
+        cpu.register.pc = 0x0800;
+        memory.data[0x0800] = 0x00;
+
+        cpu.step(&mut memory);
+
+        assert_eq!(memory.peek(0xFFFF), 0x08);
+        assert_eq!(cpu.register.pc, 0x0058);
+    }
 }
@@ -1,9 +1,8 @@
-use crate::bus::Bus;
 use crate::cpu::addresses::AddressMode;
 use crate::cpu::instructions::Instruction;
 use crate::cpu::register::RegisterType;
-use crate::cpu::{register, CPU};
-use crate::tpu::Timer;
+use crate::cpu::CPU;
+use crate::memory::Memory;
 use std::fmt;
 use std::fmt::Debug;
 
@@ -67,28 +66,32 @@ impl fmt::Display for Action {
 }
 
 impl Action {
-    pub fn execute(&self, cpu: &mut CPU, instruction: &Instruction) {
+    pub fn execute(&self, cpu: &mut CPU, instruction: &Instruction, bus: &mut dyn Memory) {
         match self {
             Action::NONE => {}
             Action::NOP => {}
             Action::LD => {
                 if cpu.dest_is_mem {
                     if cpu.register.is_16bit(instruction.register_2) {
-                        Timer::get().emu_cycles(1, cpu);
-                        Bus::get().write16(cpu.mem_dest, cpu.fetch_data, cpu);
+                        // LD (a16),SP stores the low byte first.
+                        bus.write(cpu.mem_dest, cpu.fetch_data as u8);
+                        bus.tick(1);
+                        bus.write(cpu.mem_dest.wrapping_add(1), (cpu.fetch_data >> 8) as u8);
+                        bus.tick(1);
                     } else {
-                        Bus::get().write(cpu.mem_dest, cpu.fetch_data as u8, cpu);
+                        bus.write(cpu.mem_dest, cpu.fetch_data as u8);
+                        bus.tick(1);
                     }
                 } else {
                     if *instruction.address == AddressMode::HLSPR {
                         cpu.register.set_h(
-                            ((cpu.read_register(instruction.register_2) as u8)
-                                & 0x0F + (cpu.fetch_data as u8 & 0x0F))
+                            (cpu.read_register(instruction.register_2) & 0x0F)
+                                + (cpu.fetch_data & 0x0F)
                                 >= 0x10,
                         );
                         cpu.register.set_c(
-                            ((cpu.read_register(instruction.register_2))
-                                & 0xFF00 + (cpu.fetch_data & 0xFF00))
+                            (cpu.read_register(instruction.register_2) & 0xFF)
+                                + (cpu.fetch_data & 0xFF)
                                 >= 0x100,
                         );
                         cpu.register.set_z(false);
@@ -96,61 +99,69 @@ impl Action {
 
                         cpu.set_register(
                             instruction.register_1,
-                            (cpu.read_register(instruction.register_2) as i8 + cpu.fetch_data as i8)
-                                as u16,
+                            cpu.read_register(instruction.register_2)
+                                .wrapping_add(cpu.fetch_data as u8 as i8 as u16),
                         );
+                        bus.tick(1);
                     } else {
+                        // Only LD SP,HL moves 16 bits between registers, which takes a cycle.
+                        if *instruction.register_1 == RegisterType::SP
+                            && *instruction.address == AddressMode::RR
+                        {
+                            bus.tick(1);
+                        }
+
                         cpu.set_register(instruction.register_1, cpu.fetch_data)
                     }
                 }
             }
             Action::INC => {
-                if cpu.register.is_16bit(instruction.register_1) {
-                    Timer::get().emu_cycles(1, cpu);
+                if cpu.register.is_16bit(instruction.register_1) && !cpu.dest_is_mem {
+                    bus.tick(1);
                 }
 
-                if *instruction.register_1 == RegisterType::HL
+                let val = if *instruction.register_1 == RegisterType::HL
                     && instruction.address == &AddressMode::MR
                 {
-                    let val = Bus::get()
-                        .read(cpu.read_register(instruction.register_1), &cpu)
-                        .wrapping_add(1);
-                    let val = val & 0xFF;
-
-                    Bus::get().write(cpu.read_register(instruction.register_1), val as u8, cpu)
+                    let val = (cpu.fetch_data as u8).wrapping_add(1);
+                    bus.write(cpu.mem_dest, val);
+                    bus.tick(1);
+                    val as u16
                 } else {
                     let val = cpu.read_register(instruction.register_1).wrapping_add(1);
-                    cpu.set_register(instruction.register_1, val)
-                }
+                    cpu.set_register(instruction.register_1, val);
+                    cpu.read_register(instruction.register_1)
+                };
 
                 if (cpu.current_op_code & 0x03) != 0x03 {
-                    let val = cpu.read_register(instruction.register_1);
                     cpu.register.set_z(val == 0);
                     cpu.register.set_n(false);
                     cpu.register.set_h((val & 0x0F) == 0);
                 }
             }
             Action::DEC => {
-                if cpu.register.is_16bit(instruction.register_1) {
-                    Timer::get().emu_cycles(1, cpu);
+                if cpu.register.is_16bit(instruction.register_1) && !cpu.dest_is_mem {
+                    bus.tick(1);
                 }
 
-                if *instruction.register_1 == RegisterType::HL
+                let val = if *instruction.register_1 == RegisterType::HL
                     && *instruction.address == AddressMode::MR
                 {
-                    let val = (Bus::get().read(cpu.read_register(instruction.register_1), &cpu)
-                        as u8)
-                        .wrapping_sub(1);
-
-                    Bus::get().write(cpu.read_register(instruction.register_1), val, cpu)
+                    let val = (cpu.fetch_data as u8).wrapping_sub(1);
+                    bus.write(cpu.mem_dest, val);
+                    bus.tick(1);
+                    val as u16
+                } else if cpu.register.is_16bit(instruction.register_1) {
+                    let val = cpu.read_register(instruction.register_1).wrapping_sub(1);
+                    cpu.set_register(instruction.register_1, val);
+                    val
                 } else {
                     let val = (cpu.read_register(instruction.register_1) as u8).wrapping_sub(1);
-
-                    cpu.set_register(instruction.register_1, val as u16)
-                }
+                    cpu.set_register(instruction.register_1, val as u16);
+                    val as u16
+                };
 
                 if (cpu.current_op_code & 0x0B) != 0x0B {
-                    let val = cpu.read_register(instruction.register_1);
                     cpu.register.set_z(val == 0);
                     cpu.register.set_n(true);
                     cpu.register.set_h((val & 0x0F) == 0x0F);
@@ -167,22 +178,24 @@ impl Action {
                 cpu.register.set_c(c != 0);
             }
             Action::ADD => {
-                let val: u32;
                 let is_16bit = cpu.register.is_16bit(instruction.register_1);
 
-                if is_16bit {
-                    Timer::get().emu_cycles(1, cpu);
+                // ADD HL,rr takes one extra cycle, ADD SP,e two.
+                if *instruction.register_1 == RegisterType::SP {
+                    bus.tick(2);
+                } else if is_16bit {
+                    bus.tick(1);
                 }
 
-                if *instruction.register_1 == RegisterType::SP {
-                    val = (cpu.read_register(instruction.register_1) as i8)
-                        .wrapping_add(cpu.fetch_data as i8) as u32;
+                let val: u32 = if *instruction.register_1 == RegisterType::SP {
+                    cpu.read_register(instruction.register_1)
+                        .wrapping_add(cpu.fetch_data as u8 as i8 as u16) as u32
                 } else {
-                    val = cpu.read_register(instruction.register_1) as u32 + cpu.fetch_data as u32;
-                }
+                    cpu.read_register(instruction.register_1) as u32 + cpu.fetch_data as u32
+                };
 
                 if *instruction.register_1 == RegisterType::SP {
-                    cpu.register.set_n(true);
+                    cpu.register.set_n(false);
                     cpu.register.set_z(false);
                     cpu.register.set_h(
                         (cpu.read_register(instruction.register_1) & 0xF)
@@ -195,7 +208,7 @@ impl Action {
                             >= 0x100,
                     );
                 } else if is_16bit {
-                    cpu.register.set_n(true);
+                    cpu.register.set_n(false);
                     cpu.register.set_h(
                         (cpu.read_register(instruction.register_1) & 0xFFF)
                             .wrapping_add(cpu.fetch_data & 0xFFF)
@@ -205,7 +218,7 @@ impl Action {
                         .wrapping_add(cpu.fetch_data as u32);
                     cpu.register.set_c(n >= 0x10000);
                 } else {
-                    cpu.register.set_n(true);
+                    cpu.register.set_n(false);
                     cpu.register.set_z((val & 0xFF) == 0);
                     cpu.register.set_h(
                         (cpu.read_register(instruction.register_1) & 0xF)
@@ -231,7 +244,12 @@ impl Action {
                 cpu.register.set_c(b != 0);
             }
             Action::STOP => {
-                panic!("Stop instruction called!")
+                // STOP is two bytes long, the second is ignored.
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
+
+                if !bus.stop() {
+                    cpu.stopped = true;
+                }
             }
             Action::RLA => {
                 let u = cpu.register.a as u8;
@@ -245,8 +263,8 @@ impl Action {
             }
             Action::JR => {
                 let rel = (cpu.fetch_data & 0xFF) as i8;
-                let address = (cpu.register.pc as i16 + rel as i16) as u16;
-                cpu.go_to(address, false, &instruction);
+                let address = cpu.register.pc.wrapping_add(rel as u16);
+                cpu.go_to(address, false, instruction, bus);
             }
             Action::RRA => {
                 let new_c = (cpu.register.a & 1) as u8;
@@ -273,9 +291,9 @@ impl Action {
                 }
 
                 if cpu.register.n_flag() {
-                    cpu.register.a = cpu.register.a.wrapping_sub(u);
+                    cpu.register.a = cpu.register.a.wrapping_sub(u) & 0xFF;
                 } else {
-                    cpu.register.a = cpu.register.a.wrapping_add(u);
+                    cpu.register.a = cpu.register.a.wrapping_add(u) & 0xFF;
                 }
                 cpu.register.set_z(cpu.register.a == 0);
                 cpu.register.set_h(false);
@@ -297,7 +315,13 @@ impl Action {
                 cpu.register.set_c(cpu.register.c_flag() ^ true);
             }
             Action::HALT => {
-                cpu.halted = true;
+                // With IME off and an interrupt already pending HALT exits at once, and
+                // the DMG fails to advance PC past the next opcode.
+                if !cpu.master_enabled && bus.interrupts().pending() != 0 {
+                    cpu.halt_bug = true;
+                } else {
+                    cpu.halted = true;
+                }
             }
             Action::ADC => {
                 let u = cpu.fetch_data;
@@ -307,7 +331,7 @@ impl Action {
                 cpu.register.a = (a.wrapping_add(u).wrapping_add(c)) & 0xFF;
 
                 cpu.register.set_z(cpu.register.a == 0);
-                cpu.register.set_n(true);
+                cpu.register.set_n(false);
                 cpu.register
                     .set_h((a & 0xF).wrapping_add(u & 0xF).wrapping_add(c) > 0xF);
                 cpu.register.set_c(a.wrapping_add(u).wrapping_add(c) > 0xFF);
@@ -333,7 +357,7 @@ impl Action {
             Action::SBC => {
                 let val = cpu.fetch_data + cpu.register.c_flag() as u16;
 
-                let z = (cpu.read_register(instruction.register_1).wrapping_sub(val)) == 0;
+                let z = cpu.read_register(instruction.register_1).wrapping_sub(val) & 0xFF == 0;
                 let h = (((cpu.read_register(instruction.register_1) & 0xF) as i32)
                     .wrapping_sub((cpu.fetch_data & 0xF) as i32)
                     .wrapping_sub(cpu.register.c_flag() as i32))
@@ -382,10 +406,10 @@ impl Action {
                 cpu.register.set_c(n < 0);
             }
             Action::POP => {
-                let lo = cpu.stack_pop();
-                Timer::get().emu_cycles(1, cpu);
-                let hi = cpu.stack_pop();
-                Timer::get().emu_cycles(1, cpu);
+                let lo = cpu.stack_pop(bus);
+                bus.tick(1);
+                let hi = cpu.stack_pop(bus);
+                bus.tick(1);
 
                 let num = (hi << 8) | lo;
 
@@ -395,200 +419,134 @@ impl Action {
                 }
             }
             Action::JUMP => {
-                cpu.go_to(cpu.fetch_data, false, &instruction);
+                cpu.go_to(cpu.fetch_data, false, instruction, bus);
             }
             Action::PUSH => {
                 let hi = (cpu.read_register(instruction.register_1) >> 8) & 0xFF;
-                Timer::get().emu_cycles(1, cpu);
-                cpu.stack_push(hi as u8);
+                bus.tick(1);
+                cpu.stack_push(hi as u8, bus);
 
                 let lo = cpu.read_register(instruction.register_1) & 0xFF;
-                Timer::get().emu_cycles(1, cpu);
-                cpu.stack_push(lo as u8);
+                bus.tick(1);
+                cpu.stack_push(lo as u8, bus);
 
-                Timer::get().emu_cycles(1, cpu);
+                bus.tick(1);
             }
-            Action::RET => cpu.return_from_procedure(&instruction),
+            Action::RET => cpu.return_from_procedure(instruction, bus),
             Action::CB => {
-                let op = cpu.fetch_data as u8;
-                let reg = register::reg_lookup(op & 0b111);
-                let bit = (op >> 3) & 0b111;
-                let bit_op = (op >> 6) & 0b11;
-                let mut reg_val = cpu.read_register8(&reg);
-
-                Timer::get().emu_cycles(1, cpu);
-
-                if reg == &RegisterType::HL {
-                    Timer::get().emu_cycles(2, cpu);
-                }
-
-                match bit_op {
-                    1 => {
-                        cpu.register.set_z((reg_val & (1 << bit)) != 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(true);
-                        return;
-                    }
-                    2 => {
-                        reg_val &= !(1 << bit);
-                        cpu.set_register8(&reg, reg_val);
-                        return;
-                    }
-                    3 => {
-                        reg_val |= 1 << bit;
-                        cpu.set_register8(&reg, reg_val);
-                        return;
-                    }
-                    _ => {}
-                }
-
-                match bit {
-                    0 => {
-                        let mut result = (reg_val << 1) & 0xFF;
-                        let set_c: bool;
-
-                        if (reg_val & (1 << 7)) != 0 {
-                            result |= 1;
-                            set_c = true;
-                        } else {
-                            set_c = false;
-                        }
-
-                        cpu.set_register8(&reg, result);
-                        cpu.register.set_z(result == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c(set_c);
-                        return;
-                    }
-
-                    1 => {
-                        let old = reg_val.clone();
-                        reg_val >>= 1;
-                        reg_val |= old << 7;
-
-                        cpu.set_register8(&reg, reg_val);
-                        cpu.register.set_z(reg_val == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((old & 1) != 0);
-                        return;
-                    }
-
-                    2 => {
-                        let old = reg_val.clone();
-                        reg_val <<= 1;
-
-                        let c_flag_num = if cpu.register.c_flag() { 1 } else { 0 };
-
-                        reg_val |= c_flag_num;
-                        cpu.set_register8(&reg, reg_val);
-                        cpu.register.set_z(reg_val == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((old & 0x80) != 0);
-                        return;
-                    }
-
-                    3 => {
-                        let old = reg_val.clone();
-                        reg_val >>= 1;
-                        let c_flag_num = if cpu.register.c_flag() { 1 } else { 0 };
-
-                        reg_val |= c_flag_num << 7;
-                        cpu.set_register8(&reg, reg_val);
-                        cpu.register.set_z(reg_val == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((old & 1) != 0);
-                        return;
-                    }
-
-                    4 => {
-                        let old = reg_val.clone();
-                        reg_val <<= 1;
-                        cpu.set_register8(&reg, reg_val);
-                        cpu.register.set_z(reg_val == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((old & 0x80) != 0);
-                        return;
-                    }
-
-                    5 => {
-                        let u = reg_val >> 1;
-                        cpu.set_register8(&reg, u);
-                        cpu.register.set_z(u == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((reg_val & 1) != 0);
-                        return;
-                    }
-                    6 => {
-                        reg_val = ((reg_val & 0xF0) >> 4) | ((reg_val & 0xF) << 4);
-                        cpu.set_register8(&reg, reg_val);
-                        cpu.register.set_z(reg_val == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c(false);
-                        return;
-                    }
-
-                    7 => {
-                        let u = reg_val >> 1;
-                        cpu.set_register8(&reg, u);
-                        cpu.register.set_z(u == 0);
-                        cpu.register.set_n(false);
-                        cpu.register.set_h(false);
-                        cpu.register.set_c((reg_val & 1) != 0);
-                        return;
-                    }
-
-                    _ => {
-                        panic!("ERROR: INVALID CB: {:#04x}", op)
-                    }
-                }
+                let cb = Instruction::from_cb(cpu.fetch_data as u8);
+                cb.execute(cpu, bus);
             }
             Action::CALL => {
-                cpu.go_to(cpu.fetch_data, true, &instruction);
+                cpu.go_to(cpu.fetch_data, true, instruction, bus);
             }
             Action::RETI => {
                 cpu.master_enabled = true;
-                cpu.return_from_procedure(&instruction)
+                cpu.return_from_procedure(instruction, bus)
             }
             Action::LDH => {
                 match instruction.register_1 {
                     RegisterType::A => cpu.set_register(
                         instruction.register_1,
-                        Bus::get().read(0xFF00 | cpu.fetch_data, &cpu),
+                        bus.read(0xFF00 | cpu.fetch_data) as u16,
                     ),
-                    _ => Bus::get().write(cpu.mem_dest, cpu.register.a as u8, cpu),
+                    _ => bus.write(cpu.mem_dest, cpu.register.a as u8),
                 }
 
-                Timer::get().emu_cycles(1, cpu);
+                bus.tick(1);
             }
-            Action::JPHL => {}
+            Action::JPHL => cpu.register.pc = cpu.fetch_data,
             Action::DI => {
                 cpu.master_enabled = false;
+                cpu.enabling_ime = false;
             }
             Action::EI => {
                 cpu.enabling_ime = true;
             }
             Action::RST => {
-                cpu.go_to(*instruction.param, true, &instruction);
-            }
-            Action::ERR => {}
-            Action::RLC => {}
-            Action::RRC => {}
-            Action::RL => {}
-            Action::RR => {}
-            Action::SLA => {}
-            Action::SRA => {}
-            Action::SWAP => {}
-            Action::SRL => {}
-            Action::BIT => {}
-            Action::RES => {}
-            Action::SET => {}
+                cpu.go_to(*instruction.param, true, instruction, bus);
+            }
+            Action::ERR => cpu.locked = true,
+            Action::RLC => {
+                let value = cpu.fetch_data as u8;
+                shift(
+                    cpu,
+                    instruction,
+                    value.rotate_left(1),
+                    value & 0x80 != 0,
+                    bus,
+                );
+            }
+            Action::RRC => {
+                let value = cpu.fetch_data as u8;
+                shift(
+                    cpu,
+                    instruction,
+                    value.rotate_right(1),
+                    value & 0x01 != 0,
+                    bus,
+                );
+            }
+            Action::RL => {
+                let value = cpu.fetch_data as u8;
+                let result = (value << 1) | cpu.register.c_flag() as u8;
+                shift(cpu, instruction, result, value & 0x80 != 0, bus);
+            }
+            Action::RR => {
+                let value = cpu.fetch_data as u8;
+                let result = (value >> 1) | ((cpu.register.c_flag() as u8) << 7);
+                shift(cpu, instruction, result, value & 0x01 != 0, bus);
+            }
+            Action::SLA => {
+                let value = cpu.fetch_data as u8;
+                shift(cpu, instruction, value << 1, value & 0x80 != 0, bus);
+            }
+            Action::SRA => {
+                let value = cpu.fetch_data as u8;
+                let result = (value >> 1) | (value & 0x80);
+                shift(cpu, instruction, result, value & 0x01 != 0, bus);
+            }
+            Action::SWAP => {
+                let value = cpu.fetch_data as u8;
+                shift(cpu, instruction, value.rotate_left(4), false, bus);
+            }
+            Action::SRL => {
+                let value = cpu.fetch_data as u8;
+                shift(cpu, instruction, value >> 1, value & 0x01 != 0, bus);
+            }
+            Action::BIT => {
+                cpu.register
+                    .set_z(cpu.fetch_data & (1 << *instruction.param) == 0);
+                cpu.register.set_n(false);
+                cpu.register.set_h(true);
+            }
+            Action::RES => {
+                let value = cpu.fetch_data as u8 & !(1 << *instruction.param);
+                store(cpu, instruction, value, bus);
+            }
+            Action::SET => {
+                let value = cpu.fetch_data as u8 | (1 << *instruction.param);
+                store(cpu, instruction, value, bus);
+            }
         }
     }
 }
+
+/// Stores the result of a CB rotate or shift and sets Z and C from it.
+fn shift(cpu: &mut CPU, instruction: &Instruction, result: u8, carry: bool, bus: &mut dyn Memory) {
+    store(cpu, instruction, result, bus);
+    cpu.register.set_z(result == 0);
+    cpu.register.set_n(false);
+    cpu.register.set_h(false);
+    cpu.register.set_c(carry);
+}
+
+/// Writes a CB result back to its register, or to (HL) on a cycle of its own.
+fn store(cpu: &mut CPU, instruction: &Instruction, value: u8, bus: &mut dyn Memory) {
+    if cpu.dest_is_mem {
+        bus.write(cpu.mem_dest, value);
+        bus.tick(1);
+    } else {
+        cpu.set_register(instruction.register_1, value as u16);
+    }
+}
@@ -1,8 +1,7 @@
-use crate::bus::Bus;
 use crate::cpu::instructions::Instruction;
 use crate::cpu::register::RegisterType;
 use crate::cpu::CPU;
-use crate::tpu::Timer;
+use crate::memory::Memory;
 
 #[derive(PartialEq)]
 pub enum AddressMode {
@@ -31,7 +30,7 @@ pub enum AddressMode {
 }
 
 impl AddressMode {
-    pub fn fetch(&self, cpu: &mut CPU, instruction: &Instruction) {
+    pub fn fetch(&self, cpu: &mut CPU, instruction: &Instruction, bus: &mut dyn Memory) {
         cpu.mem_dest = 0;
         cpu.dest_is_mem = false;
 
@@ -39,11 +38,11 @@ impl AddressMode {
             AddressMode::NONE => {}
             AddressMode::IMP => {}
             AddressMode::RD16 | AddressMode::D16 => {
-                let lo = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                let hi = Bus::get().read(cpu.register.pc + 1, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 2;
+                let lo = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                let hi = bus.read(cpu.register.pc.wrapping_add(1)) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(2);
 
                 cpu.fetch_data = lo | (hi << 8);
             }
@@ -53,107 +52,117 @@ impl AddressMode {
                 cpu.mem_dest = cpu.read_register(instruction.register_1);
                 cpu.dest_is_mem = true;
 
-                match instruction.register_1 {
-                    RegisterType::C => cpu.mem_dest |= 0xFF00,
-                    _ => {}
+                if instruction.register_1 == &RegisterType::C {
+                    cpu.mem_dest |= 0xFF00
                 }
             }
             AddressMode::R => cpu.fetch_data = cpu.read_register(instruction.register_1),
             AddressMode::RD8 => {
-                cpu.fetch_data = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                cpu.fetch_data = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
             }
             AddressMode::RMR => {
                 let mut address = cpu.read_register(instruction.register_2);
-                match instruction.register_1 {
-                    RegisterType::C => address |= 0xFF00,
-                    _ => {}
+                if instruction.register_2 == &RegisterType::C {
+                    address |= 0xFF00
                 }
-                cpu.fetch_data = Bus::get().read(address, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                cpu.fetch_data = bus.read(address) as u16;
+                bus.tick(1);
             }
             AddressMode::RHLI => {
-                cpu.fetch_data = Bus::get().read(cpu.read_register(instruction.register_2), &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.set_register(&RegisterType::HL, cpu.read_register(&RegisterType::HL) + 1)
+                cpu.fetch_data = bus.read(cpu.read_register(instruction.register_2)) as u16;
+                bus.tick(1);
+                cpu.set_register(
+                    &RegisterType::HL,
+                    cpu.read_register(&RegisterType::HL).wrapping_add(1),
+                )
             }
             AddressMode::RHLD => {
-                cpu.fetch_data = Bus::get().read(cpu.read_register(instruction.register_2), &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.set_register(&RegisterType::HL, cpu.read_register(&RegisterType::HL) - 1)
+                cpu.fetch_data = bus.read(cpu.read_register(instruction.register_2)) as u16;
+                bus.tick(1);
+                cpu.set_register(
+                    &RegisterType::HL,
+                    cpu.read_register(&RegisterType::HL).wrapping_sub(1),
+                )
             }
             AddressMode::HLIR => {
                 cpu.fetch_data = cpu.read_register(instruction.register_2);
                 cpu.mem_dest = cpu.read_register(instruction.register_1);
                 cpu.dest_is_mem = true;
-                cpu.set_register(&RegisterType::HL, cpu.read_register(&RegisterType::HL) + 1);
+                cpu.set_register(
+                    &RegisterType::HL,
+                    cpu.read_register(&RegisterType::HL).wrapping_add(1),
+                );
             }
             AddressMode::HLDR => {
                 cpu.fetch_data = cpu.read_register(instruction.register_2);
                 cpu.mem_dest = cpu.read_register(instruction.register_1);
                 cpu.dest_is_mem = true;
-                cpu.set_register(&RegisterType::HL, cpu.read_register(&RegisterType::HL) - 1);
+                cpu.set_register(
+                    &RegisterType::HL,
+                    cpu.read_register(&RegisterType::HL).wrapping_sub(1),
+                );
             }
             AddressMode::RA8 => {
-                cpu.fetch_data = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                cpu.fetch_data = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
             }
             AddressMode::A8R => {
-                cpu.mem_dest = Bus::get().read(cpu.register.pc, &cpu) | 0xFF00;
+                cpu.mem_dest = bus.read(cpu.register.pc) as u16 | 0xFF00;
                 cpu.dest_is_mem = true;
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
             }
             AddressMode::HLSPR => {
-                cpu.fetch_data = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                cpu.fetch_data = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
             }
             AddressMode::D8 => {
-                cpu.fetch_data = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                cpu.fetch_data = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
             }
             AddressMode::D16R | AddressMode::A16R => {
-                let lo = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                let lo = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
 
-                let hi = Bus::get().read(cpu.register.pc + 1, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                let hi = bus.read(cpu.register.pc.wrapping_add(1)) as u16;
+                bus.tick(1);
 
                 cpu.mem_dest = lo | (hi << 8);
                 cpu.dest_is_mem = true;
 
-                cpu.register.pc += 2;
+                cpu.register.pc = cpu.register.pc.wrapping_add(2);
                 cpu.fetch_data = cpu.read_register(instruction.register_2);
             }
             AddressMode::MRD8 => {
-                cpu.fetch_data = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
-                cpu.register.pc += 1;
+                cpu.fetch_data = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
+                cpu.register.pc = cpu.register.pc.wrapping_add(1);
                 cpu.mem_dest = cpu.read_register(instruction.register_1);
                 cpu.dest_is_mem = true;
             }
             AddressMode::MR => {
                 cpu.mem_dest = cpu.read_register(instruction.register_1);
                 cpu.dest_is_mem = true;
-                cpu.fetch_data = Bus::get().read(cpu.read_register(instruction.register_1), &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                cpu.fetch_data = bus.read(cpu.read_register(instruction.register_1)) as u16;
+                bus.tick(1);
             }
             AddressMode::RA16 => {
-                let lo = Bus::get().read(cpu.register.pc, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                let lo = bus.read(cpu.register.pc) as u16;
+                bus.tick(1);
 
-                let hi = Bus::get().read(cpu.register.pc + 1, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                let hi = bus.read(cpu.register.pc.wrapping_add(1)) as u16;
+                bus.tick(1);
 
                 let address = lo | (hi << 8);
 
-                cpu.register.pc += 2;
-                cpu.fetch_data = Bus::get().read(address, &cpu);
-                Timer::get().emu_cycles(1, cpu);
+                cpu.register.pc = cpu.register.pc.wrapping_add(2);
+                cpu.fetch_data = bus.read(address) as u16;
+                bus.tick(1);
             }
         }
     }
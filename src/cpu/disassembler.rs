@@ -0,0 +1,271 @@
+use crate::cpu::actions::Action;
+use crate::cpu::addresses::AddressMode;
+use crate::cpu::conditions::ConditionType;
+use crate::cpu::instructions::Instruction;
+use crate::cpu::register::RegisterType;
+use std::fmt;
+
+/// One decoded instruction.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    /// RGBDS syntax, e.g. `ld a, [hl+]` or `jr nz, $0150`.
+    pub text: String,
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(
+            f,
+            "{:04X}: {:<8} {}",
+            self.address,
+            bytes.join(" "),
+            self.text
+        )
+    }
+}
+
+/// Decodes the instruction at `address`, reading bytes through `read`.
+pub fn disassemble(read: impl Fn(u16) -> u8, address: u16) -> Disassembly {
+    let opcode = read(address);
+    let instruction = Instruction::from(opcode);
+    let length = length(&instruction);
+
+    let bytes: Vec<u8> = (0..length)
+        .map(|offset| read(address.wrapping_add(offset)))
+        .collect();
+    let d8 = bytes.get(1).copied().unwrap_or(0);
+    let d16 = u16::from_le_bytes([d8, bytes.get(2).copied().unwrap_or(0)]);
+
+    let text = match instruction.action {
+        Action::CB => cb(d8),
+        Action::NONE | Action::ERR => format!("db ${:02X}", opcode),
+        _ => base(&instruction, address.wrapping_add(length), d8, d16),
+    };
+
+    Disassembly {
+        address,
+        bytes,
+        text,
+    }
+}
+
+/// Decodes consecutive instructions from `start` up to, but not including, `end`.
+pub fn disassemble_range(read: impl Fn(u16) -> u8, start: u16, end: u16) -> Vec<Disassembly> {
+    let mut lines = Vec::new();
+    let mut address = start as u32;
+
+    while address < end as u32 {
+        let line = disassemble(&read, address as u16);
+        address += line.bytes.len() as u32;
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn length(instruction: &Instruction) -> u16 {
+    match instruction.address {
+        AddressMode::RD16 | AddressMode::D16 | AddressMode::D16R => 3,
+        AddressMode::A16R | AddressMode::RA16 => 3,
+        AddressMode::RD8 | AddressMode::D8 | AddressMode::MRD8 => 2,
+        AddressMode::RA8 | AddressMode::A8R | AddressMode::HLSPR => 2,
+        _ if matches!(instruction.action, Action::STOP) => 2,
+        _ => 1,
+    }
+}
+
+/// Formats everything but CB-prefixed and illegal opcodes. `next` is the address of
+/// the following instruction, which relative jumps are taken from.
+fn base(instruction: &Instruction, next: u16, d8: u8, d16: u16) -> String {
+    let r1 = name(instruction.register_1);
+    let r2 = name(instruction.register_2);
+
+    let mut mnemonic = match instruction.action {
+        Action::JUMP | Action::JPHL => "jp".to_string(),
+        action => action.to_string().to_lowercase(),
+    };
+
+    let operands = match instruction.address {
+        AddressMode::NONE | AddressMode::IMP => match instruction.action {
+            Action::RST => format!("${:02X}", instruction.param),
+            _ => condition(instruction.condition).unwrap_or_default(),
+        },
+        AddressMode::RD16 => format!("{}, ${:04X}", r1, d16),
+        AddressMode::D16 => with_condition(instruction.condition, format!("${:04X}", d16)),
+        AddressMode::RR => format!("{}, {}", r1, r2),
+        AddressMode::MRR if *instruction.register_1 == RegisterType::C => {
+            mnemonic = "ldh".to_string();
+            format!("[c], {}", r2)
+        }
+        AddressMode::MRR => format!("[{}], {}", r1, r2),
+        AddressMode::R => r1,
+        AddressMode::RD8 if *instruction.register_1 == RegisterType::SP => {
+            format!("sp, {}", d8 as i8)
+        }
+        AddressMode::RD8 => format!("{}, ${:02X}", r1, d8),
+        AddressMode::RMR if *instruction.register_2 == RegisterType::C => {
+            mnemonic = "ldh".to_string();
+            format!("{}, [c]", r1)
+        }
+        AddressMode::RMR => format!("{}, [{}]", r1, r2),
+        AddressMode::RHLI => format!("{}, [hl+]", r1),
+        AddressMode::RHLD => format!("{}, [hl-]", r1),
+        AddressMode::HLIR => format!("[hl+], {}", r2),
+        AddressMode::HLDR => format!("[hl-], {}", r2),
+        AddressMode::RA8 => format!("{}, [$FF{:02X}]", r1, d8),
+        AddressMode::A8R => format!("[$FF{:02X}], {}", d8, r2),
+        AddressMode::HLSPR => format!("hl, sp{:+}", d8 as i8),
+        AddressMode::D8 => {
+            let target = next.wrapping_add(d8 as i8 as u16);
+            with_condition(instruction.condition, format!("${:04X}", target))
+        }
+        AddressMode::D16R | AddressMode::A16R => format!("[${:04X}], {}", d16, r2),
+        AddressMode::MRD8 => format!("[{}], ${:02X}", r1, d8),
+        AddressMode::MR => format!("[{}]", r1),
+        AddressMode::RA16 => format!("{}, [${:04X}]", r1, d16),
+    };
+
+    if operands.is_empty() {
+        mnemonic
+    } else {
+        format!("{} {}", mnemonic, operands)
+    }
+}
+
+fn cb(op: u8) -> String {
+    let instruction = Instruction::from_cb(op);
+    let mnemonic = instruction.action.to_string().to_lowercase();
+    let target = match instruction.address {
+        AddressMode::MR => "[hl]".to_string(),
+        _ => name(instruction.register_1),
+    };
+
+    match instruction.action {
+        Action::BIT | Action::RES | Action::SET => {
+            format!("{} {}, {}", mnemonic, instruction.param, target)
+        }
+        _ => format!("{} {}", mnemonic, target),
+    }
+}
+
+fn name(register: &RegisterType) -> String {
+    format!("{:?}", register).to_lowercase()
+}
+
+fn condition(condition: &ConditionType) -> Option<String> {
+    match condition {
+        ConditionType::NONE => None,
+        ConditionType::NZ => Some("nz".to_string()),
+        ConditionType::Z => Some("z".to_string()),
+        ConditionType::NC => Some("nc".to_string()),
+        ConditionType::C => Some("c".to_string()),
+    }
+}
+
+fn with_condition(cc: &ConditionType, operand: String) -> String {
+    match condition(cc) {
+        Some(cc) => format!("{}, {}", cc, operand),
+        None => operand,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8]) -> String {
+        let mut memory = vec![0; 0x10000];
+        memory[0x0150..0x0150 + bytes.len()].copy_from_slice(bytes);
+        disassemble(|address| memory[address as usize], 0x0150).text
+    }
+
+    #[test]
+    fn test_loads() {
+        assert_eq!(text(&[0x00]), "nop");
+        assert_eq!(text(&[0x01, 0x34, 0x12]), "ld bc, $1234");
+        assert_eq!(text(&[0x02]), "ld [bc], a");
+        assert_eq!(text(&[0x06, 0x42]), "ld b, $42");
+        assert_eq!(text(&[0x08, 0x00, 0xC0]), "ld [$C000], sp");
+        assert_eq!(text(&[0x2A]), "ld a, [hl+]");
+        assert_eq!(text(&[0x32]), "ld [hl-], a");
+        assert_eq!(text(&[0x36, 0x7F]), "ld [hl], $7F");
+        assert_eq!(text(&[0x41]), "ld b, c");
+        assert_eq!(text(&[0x7E]), "ld a, [hl]");
+        assert_eq!(text(&[0xE0, 0x44]), "ldh [$FF44], a");
+        assert_eq!(text(&[0xF0, 0x00]), "ldh a, [$FF00]");
+        assert_eq!(text(&[0xE2]), "ldh [c], a");
+        assert_eq!(text(&[0xF2]), "ldh a, [c]");
+        assert_eq!(text(&[0xEA, 0x00, 0xD0]), "ld [$D000], a");
+        assert_eq!(text(&[0xFA, 0x00, 0xD0]), "ld a, [$D000]");
+        assert_eq!(text(&[0xF8, 0xFE]), "ld hl, sp-2");
+        assert_eq!(text(&[0xF9]), "ld sp, hl");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(text(&[0x09]), "add hl, bc");
+        assert_eq!(text(&[0x34]), "inc [hl]");
+        assert_eq!(text(&[0x3D]), "dec a");
+        assert_eq!(text(&[0x86]), "add a, [hl]");
+        assert_eq!(text(&[0xAF]), "xor a, a");
+        assert_eq!(text(&[0xFE, 0x90]), "cp a, $90");
+        assert_eq!(text(&[0xE8, 0x05]), "add sp, 5");
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(text(&[0x18, 0xFE]), "jr $0150");
+        assert_eq!(text(&[0x20, 0x10]), "jr nz, $0162");
+        assert_eq!(text(&[0xC3, 0x50, 0x01]), "jp $0150");
+        assert_eq!(text(&[0xDA, 0x00, 0x40]), "jp c, $4000");
+        assert_eq!(text(&[0xE9]), "jp hl");
+        assert_eq!(text(&[0xCC, 0x00, 0x20]), "call z, $2000");
+        assert_eq!(text(&[0xC0]), "ret nz");
+        assert_eq!(text(&[0xC9]), "ret");
+        assert_eq!(text(&[0xD9]), "reti");
+        assert_eq!(text(&[0xFF]), "rst $38");
+        assert_eq!(text(&[0xC1]), "pop bc");
+        assert_eq!(text(&[0xF5]), "push af");
+        assert_eq!(text(&[0x10, 0x00]), "stop");
+        assert_eq!(text(&[0x76]), "halt");
+    }
+
+    #[test]
+    fn test_cb_prefixed() {
+        assert_eq!(text(&[0xCB, 0x00]), "rlc b");
+        assert_eq!(text(&[0xCB, 0x37]), "swap a");
+        assert_eq!(text(&[0xCB, 0x3E]), "srl [hl]");
+        assert_eq!(text(&[0xCB, 0x7C]), "bit 7, h");
+        assert_eq!(text(&[0xCB, 0x86]), "res 0, [hl]");
+        assert_eq!(text(&[0xCB, 0xFF]), "set 7, a");
+    }
+
+    #[test]
+    fn test_illegal_opcodes_are_data() {
+        assert_eq!(text(&[0xD3]), "db $D3");
+        assert_eq!(text(&[0xFD]), "db $FD");
+    }
+
+    #[test]
+    fn test_range_steps_over_operands() {
+        // LD A, 0x10; LDH (0x00), A; CB SWAP A; JR -2
+        let program = [0x3E, 0x10, 0xE0, 0x00, 0xCB, 0x37, 0x18, 0xFE];
+        let lines: Vec<String> = disassemble_range(|address| program[address as usize], 0, 8)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "0000: 3E 10    ld a, $10",
+                "0002: E0 00    ldh [$FF00], a",
+                "0004: CB 37    swap a",
+                "0006: 18 FE    jr $0006",
+            ]
+        );
+    }
+}
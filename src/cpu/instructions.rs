@@ -3,6 +3,7 @@ use crate::cpu::addresses::AddressMode;
 use crate::cpu::conditions::ConditionType;
 use crate::cpu::register::RegisterType;
 use crate::cpu::CPU;
+use crate::memory::Memory;
 
 pub struct Instruction {
     pub action: &'static Action,
@@ -30,9 +31,25 @@ impl Instruction {
         }
     }
 
-    pub fn execute(&self, cpu: &mut CPU) {
-        self.address.fetch(cpu, &self);
-        self.action.execute(cpu, &self);
+    /// Decodes the byte following a 0xCB prefix.
+    pub fn from_cb(opcode: u8) -> Self {
+        let (action, address, register_1, register_2, condition, param) =
+            &CB_OP_CODES[opcode as usize];
+
+        Self {
+            action,
+            address,
+            register_1,
+            register_2,
+            condition,
+            param,
+            opcode,
+        }
+    }
+
+    pub fn execute(&self, cpu: &mut CPU, bus: &mut dyn Memory) {
+        self.address.fetch(cpu, self, bus);
+        self.action.execute(cpu, self, bus);
     }
 }
 
@@ -67,7 +84,7 @@ const OP_CODES: [(
     (Action::INC, AddressMode::R, RegisterType::DE, RegisterType::NONE, ConditionType::NONE, 0,), // 0x13
     (Action::INC, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x14
     (Action::DEC, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x15
-    (Action::LD, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x16
+    (Action::LD, AddressMode::RD8, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x16
     (Action::RLA, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0x17
     (Action::JR, AddressMode::D8, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0x18
     (Action::ADD, AddressMode::RR, RegisterType::HL, RegisterType::DE, ConditionType::NONE, 0,), // 0x19
@@ -238,7 +255,7 @@ const OP_CODES: [(
     (Action::CP, AddressMode::RMR, RegisterType::A, RegisterType::HL, ConditionType::NONE, 0,), // 0xBE
     (Action::CP, AddressMode::RR, RegisterType::A, RegisterType::A, ConditionType::NONE, 0,), // 0xBF
     (Action::RET, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NZ, 0,), // 0xC0
-    (Action::POP, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC1
+    (Action::POP, AddressMode::R, RegisterType::BC, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC1
     (Action::JUMP, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::NZ, 0,), // 0xC2
     (Action::JUMP, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC3
     (Action::CALL, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::NZ, 0,), // 0xC4
@@ -256,7 +273,7 @@ const OP_CODES: [(
     (Action::RET, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NC, 0,), // 0xD0
     (Action::POP, AddressMode::R, RegisterType::DE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xD1
     (Action::JUMP, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::NC, 0,), // 0xD2
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xD3
     (Action::CALL, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::NC, 0,), // 0xD4
     (Action::PUSH, AddressMode::R, RegisterType::DE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xD5
     (Action::SUB, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xD6
@@ -264,32 +281,32 @@ const OP_CODES: [(
     (Action::RET, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::C, 0,), // 0xD8
     (Action::RETI, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xD9
     (Action::JUMP, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::C, 0,), // 0xDA
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xDB
     (Action::CALL, AddressMode::D16, RegisterType::NONE, RegisterType::NONE, ConditionType::C, 0,), // 0xDC
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xDD
     (Action::SBC, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xDE
     (Action::RST, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0x18), // 0xDF
     (Action::LDH, AddressMode::A8R, RegisterType::NONE, RegisterType::A, ConditionType::NONE, 0,), // 0xE0
     (Action::POP, AddressMode::R, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE1
     (Action::LD, AddressMode::MRR, RegisterType::C, RegisterType::A, ConditionType::NONE, 0,), // 0xE2
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE3
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE4
     (Action::PUSH, AddressMode::R, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE5
     (Action::AND, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE6
     (Action::RST, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0x20,), // 0xE7
     (Action::ADD, AddressMode::RD8, RegisterType::SP, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE8
-    (Action::JUMP, AddressMode::R, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE9
+    (Action::JPHL, AddressMode::R, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0xE9
     (Action::LD, AddressMode::A16R, RegisterType::NONE, RegisterType::A, ConditionType::NONE, 0,), // 0xEA
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xEB
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xEC
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xED
     (Action::XOR, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xEE
     (Action::RST, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0x28), // 0xEF
     (Action::LDH, AddressMode::RA8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF0
     (Action::POP, AddressMode::R, RegisterType::AF, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF1
     (Action::LD, AddressMode::RMR, RegisterType::A, RegisterType::C, ConditionType::NONE, 0,), // 0xF2
     (Action::DI, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF3
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF4
     (Action::PUSH, AddressMode::R, RegisterType::AF, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF5
     (Action::OR, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xF6
     (Action::RST, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0x30,), // 0xF7
@@ -297,12 +314,280 @@ const OP_CODES: [(
     (Action::LD, AddressMode::RR, RegisterType::SP, RegisterType::HL, ConditionType::NONE, 0,), // 0xF9
     (Action::LD, AddressMode::RA16, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xFA
     (Action::EI, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xFB
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
-    (Action::NONE, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,),
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xFC
+    (Action::ERR, AddressMode::NONE, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0,), // 0xFD
     (Action::CP, AddressMode::RD8, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xFE
     (Action::RST, AddressMode::IMP, RegisterType::NONE, RegisterType::NONE, ConditionType::NONE, 0x38), // 0xFF
 ];
 
+/// The second byte of CB-prefixed instructions. `param` holds the bit for BIT, RES and SET.
+#[rustfmt::skip]
+const CB_OP_CODES: [(
+    Action,
+    AddressMode,
+    RegisterType,
+    RegisterType,
+    ConditionType,
+    u16,
+); 256] = [
+    (Action::RLC, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x00
+    (Action::RLC, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x01
+    (Action::RLC, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x02
+    (Action::RLC, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x03
+    (Action::RLC, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x04
+    (Action::RLC, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x05
+    (Action::RLC, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x06
+    (Action::RLC, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x07
+    (Action::RRC, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x08
+    (Action::RRC, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x09
+    (Action::RRC, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0A
+    (Action::RRC, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0B
+    (Action::RRC, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0C
+    (Action::RRC, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0D
+    (Action::RRC, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0E
+    (Action::RRC, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x0F
+    (Action::RL, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x10
+    (Action::RL, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x11
+    (Action::RL, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x12
+    (Action::RL, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x13
+    (Action::RL, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x14
+    (Action::RL, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x15
+    (Action::RL, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x16
+    (Action::RL, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x17
+    (Action::RR, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x18
+    (Action::RR, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x19
+    (Action::RR, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1A
+    (Action::RR, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1B
+    (Action::RR, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1C
+    (Action::RR, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1D
+    (Action::RR, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1E
+    (Action::RR, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x1F
+    (Action::SLA, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x20
+    (Action::SLA, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x21
+    (Action::SLA, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x22
+    (Action::SLA, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x23
+    (Action::SLA, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x24
+    (Action::SLA, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x25
+    (Action::SLA, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x26
+    (Action::SLA, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x27
+    (Action::SRA, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x28
+    (Action::SRA, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x29
+    (Action::SRA, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2A
+    (Action::SRA, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2B
+    (Action::SRA, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2C
+    (Action::SRA, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2D
+    (Action::SRA, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2E
+    (Action::SRA, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x2F
+    (Action::SWAP, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x30
+    (Action::SWAP, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x31
+    (Action::SWAP, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x32
+    (Action::SWAP, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x33
+    (Action::SWAP, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x34
+    (Action::SWAP, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x35
+    (Action::SWAP, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x36
+    (Action::SWAP, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x37
+    (Action::SRL, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x38
+    (Action::SRL, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x39
+    (Action::SRL, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3A
+    (Action::SRL, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3B
+    (Action::SRL, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3C
+    (Action::SRL, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3D
+    (Action::SRL, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3E
+    (Action::SRL, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x3F
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x40
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x41
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x42
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x43
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x44
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x45
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x46
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x47
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 1,), // 0x48
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 1,), // 0x49
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4A
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4B
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4C
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4D
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4E
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 1,), // 0x4F
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 2,), // 0x50
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 2,), // 0x51
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 2,), // 0x52
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 2,), // 0x53
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 2,), // 0x54
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 2,), // 0x55
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 2,), // 0x56
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 2,), // 0x57
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 3,), // 0x58
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 3,), // 0x59
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5A
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5B
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5C
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5D
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5E
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 3,), // 0x5F
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 4,), // 0x60
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 4,), // 0x61
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 4,), // 0x62
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 4,), // 0x63
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 4,), // 0x64
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 4,), // 0x65
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 4,), // 0x66
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 4,), // 0x67
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 5,), // 0x68
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 5,), // 0x69
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6A
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6B
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6C
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6D
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6E
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 5,), // 0x6F
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 6,), // 0x70
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 6,), // 0x71
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 6,), // 0x72
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 6,), // 0x73
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 6,), // 0x74
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 6,), // 0x75
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 6,), // 0x76
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 6,), // 0x77
+    (Action::BIT, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 7,), // 0x78
+    (Action::BIT, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 7,), // 0x79
+    (Action::BIT, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7A
+    (Action::BIT, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7B
+    (Action::BIT, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7C
+    (Action::BIT, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7D
+    (Action::BIT, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7E
+    (Action::BIT, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 7,), // 0x7F
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0x80
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0x81
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0x82
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0x83
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0x84
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0x85
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0x86
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0x87
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 1,), // 0x88
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 1,), // 0x89
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8A
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8B
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8C
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8D
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8E
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 1,), // 0x8F
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 2,), // 0x90
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 2,), // 0x91
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 2,), // 0x92
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 2,), // 0x93
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 2,), // 0x94
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 2,), // 0x95
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 2,), // 0x96
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 2,), // 0x97
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 3,), // 0x98
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 3,), // 0x99
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9A
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9B
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9C
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9D
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9E
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 3,), // 0x9F
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA0
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA1
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA2
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA3
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA4
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA5
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA6
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 4,), // 0xA7
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 5,), // 0xA8
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 5,), // 0xA9
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAA
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAB
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAC
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAD
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAE
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 5,), // 0xAF
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB0
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB1
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB2
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB3
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB4
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB5
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB6
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 6,), // 0xB7
+    (Action::RES, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 7,), // 0xB8
+    (Action::RES, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 7,), // 0xB9
+    (Action::RES, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBA
+    (Action::RES, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBB
+    (Action::RES, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBC
+    (Action::RES, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBD
+    (Action::RES, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBE
+    (Action::RES, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 7,), // 0xBF
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC0
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC1
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC2
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC3
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC4
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC5
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC6
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 0,), // 0xC7
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 1,), // 0xC8
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 1,), // 0xC9
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCA
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCB
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCC
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCD
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCE
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 1,), // 0xCF
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD0
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD1
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD2
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD3
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD4
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD5
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD6
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 2,), // 0xD7
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 3,), // 0xD8
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 3,), // 0xD9
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDA
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDB
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDC
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDD
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDE
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 3,), // 0xDF
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE0
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE1
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE2
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE3
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE4
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE5
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE6
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 4,), // 0xE7
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 5,), // 0xE8
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 5,), // 0xE9
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 5,), // 0xEA
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 5,), // 0xEB
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 5,), // 0xEC
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 5,), // 0xED
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 5,), // 0xEE
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 5,), // 0xEF
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF0
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF1
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF2
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF3
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF4
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF5
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF6
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 6,), // 0xF7
+    (Action::SET, AddressMode::R, RegisterType::B, RegisterType::NONE, ConditionType::NONE, 7,), // 0xF8
+    (Action::SET, AddressMode::R, RegisterType::C, RegisterType::NONE, ConditionType::NONE, 7,), // 0xF9
+    (Action::SET, AddressMode::R, RegisterType::D, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFA
+    (Action::SET, AddressMode::R, RegisterType::E, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFB
+    (Action::SET, AddressMode::R, RegisterType::H, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFC
+    (Action::SET, AddressMode::R, RegisterType::L, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFD
+    (Action::SET, AddressMode::MR, RegisterType::HL, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFE
+    (Action::SET, AddressMode::R, RegisterType::A, RegisterType::NONE, ConditionType::NONE, 7,), // 0xFF
+];
+
 #[cfg(test)]
 mod tests {
     use crate::cpu::actions::*;
@@ -367,4 +652,20 @@ mod tests {
 
         assert_eq!(*inst.param, 0,);
     }
+
+    #[test]
+    fn test_16_loads_an_immediate_into_d() {
+        let inst = Instruction::from(0x16);
+        assert!(matches!(inst.action, Action::LD));
+        assert!(matches!(inst.address, AddressMode::RD8));
+        assert!(matches!(inst.register_1, RegisterType::D));
+    }
+
+    #[test]
+    fn test_c1_pops_bc() {
+        let inst = Instruction::from(0xC1);
+        assert!(matches!(inst.action, Action::POP));
+        assert!(matches!(inst.address, AddressMode::R));
+        assert!(matches!(inst.register_1, RegisterType::BC));
+    }
 }
@@ -23,10 +23,12 @@ pub fn reg_lookup(index: u8) -> &'static RegisterType {
     if index > 0b111 {
         &RegisterType::NONE
     } else {
-        REGS.get(index as usize).expect(&format!(
-            "index to grab from reg_lookup should never be greater than 7 but was {}",
-            index
-        ))
+        REGS.get(index as usize).unwrap_or_else(|| {
+            panic!(
+                "index to grab from reg_lookup should never be greater than 7 but was {}",
+                index
+            )
+        })
     }
 }
 
@@ -89,15 +91,15 @@ impl Register {
     }
 
     pub fn is_16bit(&self, reg_type: &RegisterType) -> bool {
-        match reg_type {
+        matches!(
+            reg_type,
             RegisterType::AF
-            | RegisterType::BC
-            | RegisterType::DE
-            | RegisterType::HL
-            | RegisterType::SP
-            | RegisterType::PC => true,
-            _ => false,
-        }
+                | RegisterType::BC
+                | RegisterType::DE
+                | RegisterType::HL
+                | RegisterType::SP
+                | RegisterType::PC
+        )
     }
 }
 
@@ -0,0 +1,134 @@
+/// Bytes copied into OAM, one per M-cycle.
+const LENGTH: u8 = 0xA0;
+
+/// M-cycles between the write to 0xFF46 and the first byte being copied.
+const START_DELAY: u8 = 2;
+
+/// A snapshot of a running OAM DMA transfer, for debugging tools.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DmaStatus {
+    /// The first source address.
+    pub source: u16,
+    /// Bytes already copied, out of 160.
+    pub copied: u8,
+}
+
+/// OAM DMA, started by writing the source's high byte to 0xFF46. The bus does the
+/// copying, this only keeps track of where the transfer is.
+pub struct Dma {
+    register: u8,
+    starting: Option<(u8, u8)>,
+    active: Option<DmaStatus>,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            register: 0xFF,
+            starting: None,
+            active: None,
+        }
+    }
+
+    /// Schedules a transfer. One already running carries on until this one starts.
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+        self.starting = Some((value, START_DELAY));
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    pub fn status(&self) -> Option<DmaStatus> {
+        self.active
+    }
+
+    /// The address of the byte copied in the current M-cycle, while a transfer runs.
+    /// Sources past 0xDFFF read from echo RAM.
+    pub fn source(&self) -> Option<u16> {
+        self.active.map(|status| {
+            let source = status.source + status.copied as u16;
+            if source >= 0xE000 {
+                source - 0x2000
+            } else {
+                source
+            }
+        })
+    }
+
+    /// Advances by one M-cycle, after the current byte has been copied.
+    pub fn tick(&mut self) {
+        if let Some(status) = self.active.as_mut() {
+            status.copied += 1;
+            if status.copied == LENGTH {
+                self.active = None;
+            }
+        }
+
+        if let Some((source, delay)) = self.starting {
+            if delay > 1 {
+                self.starting = Some((source, delay - 1));
+            } else {
+                self.starting = None;
+                self.active = Some(DmaStatus {
+                    source: (source as u16) << 8,
+                    copied: 0,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_starts_after_the_delay_and_lasts_160_cycles() {
+        let mut dma = Dma::new();
+        dma.write(0xC1);
+        assert_eq!(dma.read(), 0xC1);
+
+        dma.tick();
+        assert_eq!(dma.source(), None);
+        dma.tick();
+
+        for copied in 0..LENGTH {
+            assert_eq!(dma.source(), Some(0xC100 + copied as u16));
+            dma.tick();
+        }
+        assert_eq!(dma.status(), None);
+    }
+
+    #[test]
+    fn test_restart_replaces_the_running_transfer_once_it_starts() {
+        let mut dma = Dma::new();
+        dma.write(0xC0);
+        for _ in 0..12 {
+            dma.tick();
+        }
+
+        dma.write(0xD0);
+        dma.tick();
+        assert_eq!(dma.source(), Some(0xC00B));
+        dma.tick();
+        assert_eq!(
+            dma.status(),
+            Some(DmaStatus {
+                source: 0xD000,
+                copied: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_high_sources_read_echo_ram() {
+        let mut dma = Dma::new();
+        dma.write(0xFE);
+        dma.tick();
+        dma.tick();
+
+        assert_eq!(dma.source(), Some(0xDE00));
+    }
+}
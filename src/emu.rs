@@ -1,62 +1,223 @@
+use crate::boot::{BootRom, BootRomError};
 use crate::bus::Bus;
-use crate::cartridge::Cartridge;
-use crate::cpu::CPU;
-use crate::ppu::PPU;
-#[allow(dead_code)]
+use crate::cartridge::{Cartridge, CartridgeError, Header};
+use crate::cpu::disassembler::{self, Disassembly};
+use crate::cpu::{Registers, CPU};
+use crate::dma::DmaStatus;
+use crate::joypad::Button;
+use crate::model::Model;
+use crate::trace::Tracer;
+use std::collections::VecDeque;
 use std::thread;
 use std::time::Duration;
 
-pub struct EMU {
+/// M-cycles in one frame: 154 lines of 456 dots, four dots to the M-cycle.
+const CYCLES_PER_FRAME: u64 = 154 * 456 / 4;
+
+/// Something the host may want to react to, collected with `Emulator::poll_event`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// The CPU ran an opcode that doesn't exist and locked up until the next reset.
+    /// `bank` is the ROM bank mapped at `pc`, 0 when running outside ROM.
+    IllegalOpcode { pc: u16, opcode: u8, bank: u16 },
+}
+
+pub struct Emulator {
     cpu: CPU,
-    ppu: PPU,
+    bus: Bus,
+    events: VecDeque<Event>,
     running: bool,
     paused: bool,
 }
 
-impl EMU {
-    pub fn from(file: &str) -> Self {
-        let cartridge = Cartridge::from(file);
-        Bus::init(cartridge);
-        let cpu = CPU::new();
-        let ppu = PPU {};
+impl Emulator {
+    /// Builds an emulator for `model` around a ROM image that is already in memory.
+    pub fn new(rom: impl Into<Vec<u8>>, model: Model) -> Result<Self, CartridgeError> {
+        let cartridge = Cartridge::from_bytes(rom, None)?;
+        Ok(Emulator::with_cartridge(cartridge, model))
+    }
+
+    pub fn from(file: &str, model: Model) -> Result<Self, CartridgeError> {
+        let cartridge = Cartridge::from(file)?;
+        Ok(Emulator::with_cartridge(cartridge, model))
+    }
 
-        EMU {
+    fn with_cartridge(cartridge: Cartridge, model: Model) -> Self {
+        let bus = Bus::new(cartridge, model);
+        let cpu = CPU::new(model);
+
+        Emulator {
             cpu,
-            ppu,
+            bus,
+            events: VecDeque::new(),
             running: false,
             paused: false,
         }
     }
 
-    pub fn test(test_num: u8) -> Self {
-        let file = match test_num {
-            1 => "..\\gb-test-roms\\cpu_instrs\\individual\\01-special.gb",
-            2 => "..\\gb-test-roms\\cpu_instrs\\individual\\02-interrupts.gb",
-            3 => "..\\gb-test-roms\\cpu_instrs\\individual\\03-op sp,hl.gb",
-            4 => "..\\gb-test-roms\\cpu_instrs\\individual\\04-op r,imm.gb",
-            5 => "..\\gb-test-roms\\cpu_instrs\\individual\\05-op rp.gb",
-            6 => "..\\gb-test-roms\\cpu_instrs\\individual\\06-ld r,r.gb",
-            7 => "..\\gb-test-roms\\cpu_instrs\\individual\\07-jr,jp,call,ret,rst.gb",
-            8 => "..\\gb-test-roms\\cpu_instrs\\individual\\08-misc instrs.gb",
-            9 => "..\\gb-test-roms\\cpu_instrs\\individual\\09-op r,r.gb",
-            10 => "..\\gb-test-roms\\cpu_instrs\\individual\\10-bit ops.gb",
-            11 => "..\\gb-test-roms\\cpu_instrs\\individual\\11-op a,(hl).gb",
-            _ => panic!("Num not implemented"),
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.events.clear();
+        let tracer = self.cpu.set_tracer(None);
+        self.cpu = if self.bus.boot_rom_mapped() {
+            CPU::power_on()
+        } else {
+            CPU::new(self.bus.model())
         };
+        self.cpu.set_tracer(tracer);
+    }
 
-        let cartridge = Cartridge::from(file);
-        Bus::init(cartridge);
-        let cpu = CPU::test();
-        let ppu = PPU {};
+    /// The emulated hardware, picked at construction or with `set_model`.
+    pub fn model(&self) -> Model {
+        self.bus.model()
+    }
 
-        EMU {
-            cpu,
-            ppu,
-            running: false,
-            paused: false,
+    /// Switches to other hardware and resets, so the game starts with that model's
+    /// registers. Fails, keeping the current model, when the inserted boot ROM is for the
+    /// other family.
+    pub fn set_model(&mut self, model: Model) -> Result<(), BootRomError> {
+        if let Some(boot_rom) = self.bus.boot_rom() {
+            boot_rom.check(model)?;
         }
+
+        self.bus.set_model(model);
+        self.reset();
+        Ok(())
+    }
+
+    /// Inserts a DMG (256 bytes) or CGB (2304 bytes) boot ROM image and resets, so that
+    /// it runs from 0x0000 before handing over to the cartridge. The image has to be the
+    /// one the current model runs.
+    pub fn set_boot_rom(&mut self, image: impl Into<Vec<u8>>) -> Result<(), BootRomError> {
+        self.insert_boot_rom(BootRom::new(image)?)
+    }
+
+    pub fn load_boot_rom(&mut self, file: &str) -> Result<(), BootRomError> {
+        self.insert_boot_rom(BootRom::from(file)?)
+    }
+
+    fn insert_boot_rom(&mut self, boot_rom: BootRom) -> Result<(), BootRomError> {
+        boot_rom.check(self.bus.model())?;
+        self.bus.set_boot_rom(Some(boot_rom));
+        self.reset();
+        Ok(())
+    }
+
+    /// Removes the boot ROM and resets straight into the cartridge.
+    pub fn remove_boot_rom(&mut self) {
+        self.bus.set_boot_rom(None);
+        self.reset();
+    }
+
+    /// Runs a single instruction, or one M-cycle when halted or stopped, and returns the
+    /// M-cycles it took.
+    pub fn step_instruction(&mut self) -> u64 {
+        let start = self.bus.cycles();
+        self.step();
+        self.bus.cycles() - start
+    }
+
+    /// The oldest event not yet collected.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn step(&mut self) {
+        let locked = self.cpu.illegal_opcode().is_some();
+        self.cpu.step(&mut self.bus);
+
+        if let (false, Some((pc, opcode))) = (locked, self.cpu.illegal_opcode()) {
+            let bank = self.bus.rom_bank(pc);
+            self.events
+                .push_back(Event::IllegalOpcode { pc, opcode, bank });
+        }
+    }
+
+    /// Runs until the PPU enters VBlank. With the LCD off no frame is ever finished, so
+    /// this gives up after one frame's worth of cycles. After STOP nothing runs until a
+    /// button is pressed.
+    pub fn run_frame(&mut self) {
+        let frame = self.bus.frames();
+        let start = self.bus.cycles();
+
+        while self.bus.frames() == frame
+            && self.bus.cycles() - start < CYCLES_PER_FRAME
+            && !self.cpu.is_stopped()
+        {
+            self.step();
+        }
+    }
+
+    /// Whether the CPU executed STOP and waits for a button.
+    pub fn is_stopped(&self) -> bool {
+        self.cpu.is_stopped()
+    }
+
+    /// The last rendered frame, 160x144 shades from 0 (lightest) to 3 (darkest).
+    pub fn framebuffer(&self) -> &[u8] {
+        self.bus.framebuffer()
+    }
+
+    /// Audio samples produced since the last frame. Sound is not emulated yet, so this is
+    /// always empty.
+    pub fn audio_buffer(&self) -> &[f32] {
+        &[]
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.bus.set_button(button, pressed);
     }
 
+    /// Every byte sent over the link port so far. Test ROMs print their results here.
+    pub fn serial_output(&self) -> &[u8] {
+        self.bus.serial_output()
+    }
+
+    /// Attaches a tracer that logs every instruction from now on, or detaches it with
+    /// `None`. Returns the previous tracer so it can be finished.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        self.cpu.set_tracer(tracer)
+    }
+
+    /// The file the ROM was loaded from, or the header title for in-memory images.
+    pub fn cartridge_name(&self) -> &str {
+        self.bus.cartridge().name()
+    }
+
+    pub fn cartridge_header(&self) -> &Header {
+        self.bus.cartridge().header()
+    }
+
+    /// The OAM DMA transfer in progress, if any.
+    pub fn dma_status(&self) -> Option<DmaStatus> {
+        self.bus.dma_status()
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.cpu.registers()
+    }
+
+    pub fn read_memory(&self, address: u16) -> u8 {
+        self.bus.read(address)
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.bus.write(address, value);
+    }
+
+    /// Decodes the instruction at `address` without side effects.
+    pub fn disassemble(&self, address: u16) -> Disassembly {
+        disassembler::disassemble(|address| self.bus.read(address), address)
+    }
+
+    /// Decodes the instructions from `start` up to, but not including, `end`.
+    pub fn disassemble_range(&self, start: u16, end: u16) -> Vec<Disassembly> {
+        disassembler::disassemble_range(|address| self.bus.read(address), start, end)
+    }
+
+    /// Runs until the CPU locks up or executes STOP. The illegal opcode event stays
+    /// queued for `poll_event`. Nothing here can press a button, so a stopped CPU ends
+    /// the loop instead of spinning forever.
     pub fn run(&mut self) {
         self.running = true;
 
@@ -66,7 +227,391 @@ impl EMU {
                 continue;
             }
 
-            self.cpu.step();
+            self.step();
+
+            if !self.events.is_empty() || self.cpu.is_stopped() {
+                self.running = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ROM only image with `program` at the entry point and a valid header.
+    fn rom_with_program(program: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x100 + program.len()].copy_from_slice(program);
+        rom[0x14D] = 0xE7;
+        rom
+    }
+
+    fn title(emulation: &Emulator) -> String {
+        (0x134..0x13D)
+            .map(|address| emulation.bus.read(address) as char)
+            .collect()
+    }
+
+    #[test]
+    fn test_emulators_run_side_by_side() {
+        let handles: Vec<_> = ["test_roms/dmg-acid2.test", "test_roms/01-special.test"]
+            .into_iter()
+            .map(|file| {
+                thread::spawn(move || {
+                    let mut emulation = Emulator::from(file, Model::DMG).unwrap();
+                    for _ in 0..1000 {
+                        emulation.cpu.step(&mut emulation.bus);
+                    }
+                    title(&emulation)
+                })
+            })
+            .collect();
+
+        let titles: Vec<String> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(titles[0], "DMG-ACID2");
+        assert_eq!(titles[1], "\0".repeat(9));
+    }
+
+    #[test]
+    fn test_reset_restores_the_timer() {
+        let mut emulation = Emulator::from("test_roms/01-special.test", Model::DMG).unwrap();
+        let div = emulation.bus.read(0xFF04);
+
+        for _ in 0..1000 {
+            emulation.cpu.step(&mut emulation.bus);
         }
+        assert_ne!(emulation.bus.read(0xFF04), div);
+
+        emulation.reset();
+        assert_eq!(emulation.bus.read(0xFF04), div);
+    }
+
+    #[test]
+    fn test_new_from_rom_bytes() {
+        let rom = std::fs::read("test_roms/dmg-acid2.test").unwrap();
+        let mut emulator = Emulator::new(&rom[..], Model::DMG).unwrap();
+
+        assert_eq!(emulator.registers().pc, 0x0100);
+        emulator.step_instruction();
+        assert_eq!(emulator.registers().pc, 0x0101);
+        assert_eq!(emulator.read_memory(0x0100), 0x00);
+    }
+
+    /// FNV-1a, so the reference below doesn't depend on the standard library's hasher.
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        })
+    }
+
+    #[test]
+    fn test_dmg_acid2_renders_the_reference_frame() {
+        let mut emulator = Emulator::from("test_roms/dmg-acid2.test", Model::DMG).unwrap();
+        for _ in 0..30 {
+            emulator.run_frame();
+        }
+
+        assert_eq!(fnv1a(emulator.framebuffer()), 0xF272_A8FF_E3DB_4C16);
+    }
+
+    #[test]
+    fn test_run_frame_advances_one_frame() {
+        // JR -2 with the LCD left on
+        let mut emulator = Emulator::new(rom_with_program(&[0x18, 0xFE]), Model::DMG).unwrap();
+
+        emulator.run_frame();
+        let frame = emulator.bus.frames();
+        emulator.run_frame();
+
+        assert_eq!(emulator.bus.frames(), frame + 1);
+        assert_eq!(emulator.framebuffer().len(), 160 * 144);
+    }
+
+    #[test]
+    fn test_button_state_is_visible_to_the_game() {
+        let mut emulator = Emulator::from("test_roms/dmg-acid2.test", Model::DMG).unwrap();
+        emulator.write_memory(0xFF00, 0x10);
+        emulator.set_button(Button::Start, true);
+
+        assert_eq!(emulator.read_memory(0xFF00) & 0x0F, 0x07);
+        assert_eq!(emulator.read_memory(0xFF0F) & 0x10, 0x10);
+    }
+
+    #[test]
+    fn test_joypad_interrupt_enabled_through_ie_fires() {
+        // EI; then spin on JR -2
+        let mut emulator =
+            Emulator::new(rom_with_program(&[0xFB, 0x18, 0xFE]), Model::DMG).unwrap();
+        emulator.write_memory(0xFFFF, 0x10);
+        emulator.write_memory(0xFF00, 0x10);
+
+        for _ in 0..4 {
+            emulator.step_instruction();
+        }
+        assert_ne!(emulator.registers().pc, 0x0060);
+
+        emulator.set_button(Button::A, true);
+        emulator.step_instruction();
+        assert_eq!(emulator.registers().pc, 0x0060);
+    }
+
+    /// Runs one of Blargg's test ROMs until it reports over serial.
+    fn blargg(file: &str, steps: u64) -> String {
+        let mut emulator = Emulator::from(file, Model::DMG).unwrap();
+
+        for _ in 0..steps {
+            let output = emulator.serial_output();
+            if output.windows(6).any(|w| w == b"Passed" || w == b"Failed") {
+                break;
+            }
+            emulator.step_instruction();
+        }
+
+        String::from_utf8_lossy(emulator.serial_output()).into_owned()
+    }
+
+    #[test]
+    fn test_blargg_interrupts_passes() {
+        let output = blargg("test_roms/02-interrupts.test", 1_000_000);
+        assert!(output.contains("Passed"), "{}", output);
+    }
+
+    #[test]
+    fn test_blargg_mem_timing_passes() {
+        let output = blargg("test_roms/mem_timing.test", 2_000_000);
+        assert!(output.contains("Passed"), "{}", output);
+    }
+
+    #[test]
+    fn test_tracer_streams_each_instruction() {
+        let file = std::env::temp_dir().join(format!("rustboy-trace-{}.txt", std::process::id()));
+        // NOP; LD A, 0x42; HALT with nothing enabled
+        let mut emulator =
+            Emulator::new(rom_with_program(&[0x00, 0x3E, 0x42, 0x76]), Model::DMG).unwrap();
+        emulator.set_tracer(Some(Tracer::new(std::fs::File::create(&file).unwrap())));
+
+        for _ in 0..6 {
+            emulator.step_instruction();
+        }
+        emulator.set_tracer(None).unwrap().finish().unwrap();
+
+        let trace = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(
+            trace.lines().collect::<Vec<_>>(),
+            [
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,3E,42,76",
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:3E,42,76,00",
+                "A:42 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0103 PCMEM:76,00,00,00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_the_entry_point() {
+        let emulator = Emulator::from("test_roms/01-special.test", Model::DMG).unwrap();
+
+        let lines: Vec<String> = emulator
+            .disassemble_range(0x0100, 0x0104)
+            .iter()
+            .map(|line| line.text.clone())
+            .collect();
+        assert_eq!(lines, ["nop", "jp $0213"]);
+        assert_eq!(emulator.disassemble(0x0101).bytes, [0xC3, 0x13, 0x02]);
+    }
+
+    #[test]
+    fn test_illegal_opcode_locks_up_and_is_reported() {
+        // EI; LD A, 0x01; LDH (0xFF), A; 0xDD
+        let program = [0xFB, 0x3E, 0x01, 0xE0, 0xFF, 0xDD, 0x04];
+        let mut emulator = Emulator::new(rom_with_program(&program), Model::DMG).unwrap();
+        // The boot ROM leaves VBlank pending.
+        emulator.write_memory(0xFF0F, 0x00);
+
+        for _ in 0..4 {
+            emulator.step_instruction();
+        }
+        assert_eq!(
+            emulator.poll_event(),
+            Some(Event::IllegalOpcode {
+                pc: 0x0105,
+                opcode: 0xDD,
+                bank: 0
+            })
+        );
+
+        // Nothing runs afterwards, not even the VBlank interrupt, but time goes on.
+        emulator.run_frame();
+        emulator.run_frame();
+        assert_eq!(emulator.registers().pc, 0x0106);
+        assert_eq!(emulator.registers().b, 0x00);
+        assert_eq!(emulator.read_memory(0xFF0F) & 0x01, 0x01);
+        assert_eq!(emulator.poll_event(), None);
+
+        emulator.reset();
+        emulator.step_instruction();
+        assert_eq!(emulator.registers().pc, 0x0101);
+    }
+
+    #[test]
+    fn test_run_leaves_the_illegal_opcode_event_queued() {
+        let mut emulator = Emulator::new(rom_with_program(&[0x00, 0xDD]), Model::DMG).unwrap();
+
+        emulator.run();
+        assert_eq!(
+            emulator.poll_event(),
+            Some(Event::IllegalOpcode {
+                pc: 0x0101,
+                opcode: 0xDD,
+                bank: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_stop_waits_for_a_button() {
+        // LD A, 0x10; LDH (0x00), A; STOP; INC B
+        let program = [0x3E, 0x10, 0xE0, 0x00, 0x10, 0x00, 0x04];
+        let mut emulator = Emulator::new(rom_with_program(&program), Model::DMG).unwrap();
+
+        for _ in 0..3 {
+            emulator.step_instruction();
+        }
+        emulator.run_frame();
+        assert_eq!(emulator.registers().pc, 0x0106);
+        assert_eq!(emulator.read_memory(0xFF04), 0);
+        assert_eq!(emulator.step_instruction(), 1);
+
+        emulator.set_button(Button::Start, true);
+        emulator.step_instruction();
+        assert_eq!(emulator.registers().b, 0x01);
+    }
+
+    #[test]
+    fn test_run_returns_once_stopped() {
+        // LD A, 0x10; LDH (0x00), A; STOP
+        let program = [0x3E, 0x10, 0xE0, 0x00, 0x10, 0x00];
+        let mut emulator = Emulator::new(rom_with_program(&program), Model::DMG).unwrap();
+
+        emulator.run();
+        assert_eq!(emulator.registers().pc, 0x0106);
+    }
+
+    #[test]
+    fn test_boot_rom_runs_then_hands_over_to_the_cartridge() {
+        // JP 0x00FC, then LD A, 0x01; LDH (0x50), A at the very end
+        let mut boot = vec![0; 0x100];
+        boot[..3].copy_from_slice(&[0xC3, 0xFC, 0x00]);
+        boot[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let mut emulator = Emulator::new(rom_with_program(&[0x04]), Model::DMG).unwrap();
+        emulator.set_boot_rom(boot).unwrap();
+
+        assert_eq!(emulator.registers().pc, 0x0000);
+        assert_eq!(emulator.registers().sp, 0x0000);
+        assert_eq!(emulator.read_memory(0x0000), 0xC3);
+        assert_eq!(emulator.read_memory(0xFF40), 0x00);
+        assert_eq!(emulator.read_memory(0xFF04), 0x00);
+
+        for _ in 0..3 {
+            emulator.step_instruction();
+        }
+        assert_eq!(emulator.registers().pc, 0x0100);
+        assert_eq!(emulator.read_memory(0x0000), 0x00);
+        assert_eq!(emulator.read_memory(0xFF50), 0xFF);
+
+        emulator.step_instruction();
+        assert_eq!(emulator.registers().b, 0x01);
+
+        emulator.reset();
+        assert_eq!(emulator.registers().pc, 0x0000);
+        assert_eq!(emulator.read_memory(0x0000), 0xC3);
+
+        emulator.remove_boot_rom();
+        assert_eq!(emulator.registers().pc, 0x0100);
+        assert_eq!(emulator.read_memory(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_boot_rom_of_the_wrong_size_is_rejected() {
+        let mut emulator = Emulator::new(rom_with_program(&[0x04]), Model::DMG).unwrap();
+
+        let error = emulator.set_boot_rom(vec![0; 0x101]).unwrap_err();
+        assert!(matches!(error, BootRomError::BadSize(0x101)));
+        assert_eq!(emulator.registers().pc, 0x0100);
+    }
+
+    #[test]
+    fn test_boot_rom_for_the_other_model_is_rejected() {
+        let mut emulator = Emulator::new(rom_with_program(&[0x04]), Model::DMG).unwrap();
+
+        let error = emulator.set_boot_rom(vec![0; 0x900]).unwrap_err();
+        assert!(matches!(
+            error,
+            BootRomError::WrongModel {
+                size: 0x900,
+                model: Model::DMG
+            }
+        ));
+        assert_eq!(emulator.registers().pc, 0x0100);
+
+        emulator.set_boot_rom(vec![0; 0x100]).unwrap();
+        assert!(emulator.set_model(Model::CGB).is_err());
+        assert_eq!(emulator.model(), Model::DMG);
+        assert_eq!(emulator.registers().pc, 0x0000);
+    }
+
+    #[test]
+    fn test_without_a_boot_rom_io_starts_in_the_post_boot_state() {
+        let emulator = Emulator::new(rom_with_program(&[0x04]), Model::DMG).unwrap();
+
+        assert_eq!(emulator.read_memory(0xFF0F), 0xE1);
+        assert_eq!(emulator.read_memory(0xFF11), 0xBF);
+        assert_eq!(emulator.read_memory(0xFF26), 0xF1);
+        assert_eq!(emulator.read_memory(0xFF40), 0x91);
+        assert_eq!(emulator.read_memory(0xFF47), 0xFC);
+    }
+
+    #[test]
+    fn test_model_is_picked_at_construction() {
+        let mut rom = rom_with_program(&[0x04]);
+        rom[0x143] = 0x80;
+        rom[0x14D] = 0x67;
+
+        let dmg = Emulator::new(&rom[..], Model::DMG).unwrap();
+        assert_eq!(dmg.model(), Model::DMG);
+        assert_eq!(dmg.registers().a, 0x01);
+        assert_eq!(dmg.read_memory(0xFF70), 0xFF);
+
+        let cgb = Emulator::new(rom, Model::CGB).unwrap();
+        assert_eq!(cgb.model(), Model::CGB);
+        assert_eq!(cgb.registers().a, 0x11);
+        assert_eq!(cgb.read_memory(0xFF04), 0x1E);
+        assert_eq!(cgb.read_memory(0xFF70), 0xF8);
+    }
+
+    #[test]
+    fn test_set_model_restarts_with_its_registers_and_io() {
+        let mut emulator = Emulator::new(rom_with_program(&[0x04]), Model::DMG).unwrap();
+        emulator.step_instruction();
+
+        emulator.set_model(Model::SGB2).unwrap();
+        assert_eq!(emulator.registers().pc, 0x0100);
+        assert_eq!(emulator.registers().a, 0xFF);
+        assert_eq!(emulator.registers().c, 0x14);
+        assert_eq!(emulator.read_memory(0xFF04), 0xD8);
+        assert_eq!(emulator.read_memory(0xFF26), 0xF0);
+        assert_eq!(emulator.read_memory(0xFF70), 0xFF);
+
+        emulator.set_model(Model::AGB).unwrap();
+        assert_eq!(emulator.registers().b, 0x01);
+        assert_eq!(emulator.read_memory(0xFF04), 0x1E);
+        assert_eq!(emulator.read_memory(0xFF02), 0x7F);
+        assert_eq!(emulator.read_memory(0xFF70), 0xF8);
     }
 }
@@ -6,18 +6,97 @@ pub enum Interrupt {
     JoyPad,
 }
 
-const V_BLANK: u8 = 1;
-const LCD_STRAT: u8 = 2;
+pub const V_BLANK: u8 = 1;
+pub const LCD_STAT: u8 = 2;
 pub const TIMER: u8 = 4;
-const SERIAL: u8 = 8;
-const JOY_PAD: u8 = 16;
+pub const SERIAL: u8 = 8;
+pub const JOY_PAD: u8 = 16;
 
 pub fn fetch_interrupt_num(interrupt: Interrupt) -> u8 {
     match interrupt {
         Interrupt::VBlank => V_BLANK,
-        Interrupt::LCDStat => LCD_STRAT,
+        Interrupt::LCDStat => LCD_STAT,
         Interrupt::Timer => TIMER,
         Interrupt::Serial => SERIAL,
         Interrupt::JoyPad => JOY_PAD,
     }
 }
+
+/// IF and IE, the one copy of the interrupt state. Peripherals request interrupts
+/// here, the bus maps both registers and the CPU dispatches from them. Only the low five
+/// bits of IF exist, the rest read back as 1.
+pub struct Interrupts {
+    flags: u8,
+    enable: u8,
+}
+
+impl Interrupts {
+    pub fn new() -> Self {
+        Self {
+            flags: 0,
+            enable: 0,
+        }
+    }
+
+    pub fn request(&mut self, interrupts: u8) {
+        self.flags |= interrupts & 0x1F;
+    }
+
+    /// Clears a request once the CPU has dispatched it.
+    pub fn acknowledge(&mut self, interrupt: u8) {
+        self.flags &= !interrupt;
+    }
+
+    /// Interrupts that are both requested and enabled.
+    pub fn pending(&self) -> u8 {
+        self.flags & self.enable & 0x1F
+    }
+
+    pub fn read(&self) -> u8 {
+        0xE0 | self.flags
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.flags = value & 0x1F;
+    }
+
+    pub fn read_enable(&self) -> u8 {
+        self.enable
+    }
+
+    pub fn write_enable(&mut self, value: u8) {
+        self.enable = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upper_bits_read_as_one() {
+        let mut interrupts = Interrupts::new();
+        assert_eq!(interrupts.read(), 0xE0);
+
+        interrupts.write(0xFF);
+        assert_eq!(interrupts.read(), 0xFF);
+
+        interrupts.write(0x00);
+        interrupts.request(TIMER | JOY_PAD);
+        assert_eq!(interrupts.read(), 0xF4);
+    }
+
+    #[test]
+    fn test_only_enabled_requests_are_pending() {
+        let mut interrupts = Interrupts::new();
+        interrupts.request(V_BLANK | TIMER);
+        assert_eq!(interrupts.pending(), 0);
+
+        interrupts.write_enable(TIMER | SERIAL);
+        assert_eq!(interrupts.pending(), TIMER);
+
+        interrupts.acknowledge(TIMER);
+        assert_eq!(interrupts.pending(), 0);
+        assert_eq!(interrupts.read(), 0xE0 | V_BLANK);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::model::Model;
+
+/// One register in 0xFF00-0xFF7F. Bits outside `readable` always read 1, and only the
+/// bits in `writable` can be changed. A register with nothing writable ignores writes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IoRegister {
+    pub address: u16,
+    pub name: &'static str,
+    pub readable: u8,
+    pub writable: u8,
+    /// Only present on CGB. A DMG treats it as unused.
+    pub cgb: bool,
+}
+
+const fn register(address: u16, name: &'static str, readable: u8, writable: u8) -> IoRegister {
+    IoRegister {
+        address,
+        name,
+        readable,
+        writable,
+        cgb: false,
+    }
+}
+
+const fn cgb(address: u16, name: &'static str, readable: u8, writable: u8) -> IoRegister {
+    IoRegister {
+        cgb: true,
+        ..register(address, name, readable, writable)
+    }
+}
+
+#[rustfmt::skip]
+const REGISTERS: [IoRegister; 79] = [
+    register(0xFF00, "P1", 0x3F, 0x30),
+    register(0xFF01, "SB", 0xFF, 0xFF),
+    register(0xFF02, "SC", 0x81, 0x81),
+    register(0xFF04, "DIV", 0xFF, 0xFF),
+    register(0xFF05, "TIMA", 0xFF, 0xFF),
+    register(0xFF06, "TMA", 0xFF, 0xFF),
+    register(0xFF07, "TAC", 0x07, 0x07),
+    register(0xFF0F, "IF", 0x1F, 0x1F),
+    register(0xFF10, "NR10", 0x7F, 0x7F),
+    register(0xFF11, "NR11", 0xC0, 0xFF),
+    register(0xFF12, "NR12", 0xFF, 0xFF),
+    register(0xFF13, "NR13", 0x00, 0xFF),
+    register(0xFF14, "NR14", 0x40, 0xC7),
+    register(0xFF16, "NR21", 0xC0, 0xFF),
+    register(0xFF17, "NR22", 0xFF, 0xFF),
+    register(0xFF18, "NR23", 0x00, 0xFF),
+    register(0xFF19, "NR24", 0x40, 0xC7),
+    register(0xFF1A, "NR30", 0x80, 0x80),
+    register(0xFF1B, "NR31", 0x00, 0xFF),
+    register(0xFF1C, "NR32", 0x60, 0x60),
+    register(0xFF1D, "NR33", 0x00, 0xFF),
+    register(0xFF1E, "NR34", 0x40, 0xC7),
+    register(0xFF20, "NR41", 0x00, 0x3F),
+    register(0xFF21, "NR42", 0xFF, 0xFF),
+    register(0xFF22, "NR43", 0xFF, 0xFF),
+    register(0xFF23, "NR44", 0x40, 0xC0),
+    register(0xFF24, "NR50", 0xFF, 0xFF),
+    register(0xFF25, "NR51", 0xFF, 0xFF),
+    register(0xFF26, "NR52", 0x8F, 0x80),
+    register(0xFF30, "WAVE0", 0xFF, 0xFF),
+    register(0xFF31, "WAVE1", 0xFF, 0xFF),
+    register(0xFF32, "WAVE2", 0xFF, 0xFF),
+    register(0xFF33, "WAVE3", 0xFF, 0xFF),
+    register(0xFF34, "WAVE4", 0xFF, 0xFF),
+    register(0xFF35, "WAVE5", 0xFF, 0xFF),
+    register(0xFF36, "WAVE6", 0xFF, 0xFF),
+    register(0xFF37, "WAVE7", 0xFF, 0xFF),
+    register(0xFF38, "WAVE8", 0xFF, 0xFF),
+    register(0xFF39, "WAVE9", 0xFF, 0xFF),
+    register(0xFF3A, "WAVEA", 0xFF, 0xFF),
+    register(0xFF3B, "WAVEB", 0xFF, 0xFF),
+    register(0xFF3C, "WAVEC", 0xFF, 0xFF),
+    register(0xFF3D, "WAVED", 0xFF, 0xFF),
+    register(0xFF3E, "WAVEE", 0xFF, 0xFF),
+    register(0xFF3F, "WAVEF", 0xFF, 0xFF),
+    register(0xFF40, "LCDC", 0xFF, 0xFF),
+    register(0xFF41, "STAT", 0x7F, 0x78),
+    register(0xFF42, "SCY", 0xFF, 0xFF),
+    register(0xFF43, "SCX", 0xFF, 0xFF),
+    register(0xFF44, "LY", 0xFF, 0x00),
+    register(0xFF45, "LYC", 0xFF, 0xFF),
+    register(0xFF46, "DMA", 0xFF, 0xFF),
+    register(0xFF47, "BGP", 0xFF, 0xFF),
+    register(0xFF48, "OBP0", 0xFF, 0xFF),
+    register(0xFF49, "OBP1", 0xFF, 0xFF),
+    register(0xFF4A, "WY", 0xFF, 0xFF),
+    register(0xFF4B, "WX", 0xFF, 0xFF),
+    cgb(0xFF4C, "KEY0", 0x0C, 0x0C),
+    cgb(0xFF4D, "KEY1", 0x81, 0x01),
+    cgb(0xFF4F, "VBK", 0x01, 0x01),
+    register(0xFF50, "BANK", 0x00, 0x01),
+    cgb(0xFF51, "HDMA1", 0x00, 0xFF),
+    cgb(0xFF52, "HDMA2", 0x00, 0xF0),
+    cgb(0xFF53, "HDMA3", 0x00, 0x1F),
+    cgb(0xFF54, "HDMA4", 0x00, 0xF0),
+    cgb(0xFF55, "HDMA5", 0xFF, 0xFF),
+    cgb(0xFF56, "RP", 0xC3, 0xC1),
+    cgb(0xFF68, "BCPS", 0xBF, 0xBF),
+    cgb(0xFF69, "BCPD", 0xFF, 0xFF),
+    cgb(0xFF6A, "OCPS", 0xBF, 0xBF),
+    cgb(0xFF6B, "OCPD", 0xFF, 0xFF),
+    cgb(0xFF6C, "OPRI", 0x01, 0x01),
+    cgb(0xFF70, "SVBK", 0x07, 0x07),
+    cgb(0xFF72, "FF72", 0xFF, 0xFF),
+    cgb(0xFF73, "FF73", 0xFF, 0xFF),
+    cgb(0xFF74, "FF74", 0xFF, 0xFF),
+    cgb(0xFF75, "FF75", 0x70, 0x70),
+    cgb(0xFF76, "PCM12", 0xFF, 0x00),
+    cgb(0xFF77, "PCM34", 0xFF, 0x00),
+];
+
+/// Registers a CGB implements more bits of, which replace the entries above there.
+const CGB_VARIANTS: [IoRegister; 1] = [cgb(0xFF02, "SC", 0x83, 0x83)];
+
+const fn unused(address: u16) -> IoRegister {
+    register(address, "-", 0x00, 0x00)
+}
+
+const fn map() -> [IoRegister; 0x80] {
+    let mut map = [unused(0xFF00); 0x80];
+
+    let mut index = 0;
+    while index < map.len() {
+        map[index] = unused(0xFF00 + index as u16);
+        index += 1;
+    }
+
+    let mut index = 0;
+    while index < REGISTERS.len() {
+        let register = REGISTERS[index];
+        map[(register.address - 0xFF00) as usize] = register;
+        index += 1;
+    }
+
+    map
+}
+
+/// Every address in 0xFF00-0xFF7F, unused ones included.
+static MAP: [IoRegister; 0x80] = map();
+
+/// The register at `address`, which must be in 0xFF00-0xFF7F, as seen by `model`.
+pub fn lookup(address: u16, model: Model) -> IoRegister {
+    let register = MAP[(address - 0xFF00) as usize];
+
+    if model.is_cgb() {
+        CGB_VARIANTS
+            .into_iter()
+            .find(|variant| variant.address == address)
+            .unwrap_or(register)
+    } else if register.cgb {
+        unused(address)
+    } else {
+        register
+    }
+}
+
+/// Registers every boot ROM leaves behind the same way and that no peripheral sets up
+/// itself, as `(address, value)`.
+#[rustfmt::skip]
+const POST_BOOT: [(u16, u8); 21] = [
+    (0xFF0F, 0xE1),
+    (0xFF10, 0x80), (0xFF11, 0xBF), (0xFF12, 0xF3), (0xFF13, 0xFF), (0xFF14, 0xBF),
+    (0xFF16, 0x3F), (0xFF17, 0x00), (0xFF18, 0xFF), (0xFF19, 0xBF),
+    (0xFF1A, 0x7F), (0xFF1B, 0xFF), (0xFF1C, 0x9F), (0xFF1D, 0xFF), (0xFF1E, 0xBF),
+    (0xFF20, 0xFF), (0xFF21, 0x00), (0xFF22, 0x00), (0xFF23, 0xBF),
+    (0xFF24, 0x77), (0xFF25, 0xF3),
+];
+
+/// The registers `model`'s boot ROM leaves behind, applied when the emulator starts
+/// without one. The SGB boot ROM turns channel 1 off, and on CGB SC keeps its clock bit.
+pub fn post_boot(model: Model) -> impl Iterator<Item = (u16, u8)> {
+    let sc = if model.is_cgb() { 0x7F } else { 0x7E };
+    let nr52 = if model.is_sgb() { 0xF0 } else { 0xF1 };
+
+    POST_BOOT.into_iter().chain([(0xFF02, sc), (0xFF26, nr52)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers_are_sorted_and_in_range() {
+        for pair in REGISTERS.windows(2) {
+            assert!(pair[0].address < pair[1].address, "{:?}", pair[1]);
+        }
+        assert!(REGISTERS
+            .iter()
+            .all(|r| (0xFF00..0xFF80).contains(&r.address)));
+    }
+
+    #[test]
+    fn test_unlisted_addresses_are_unused() {
+        let register = lookup(0xFF03, Model::DMG);
+        assert_eq!(register.address, 0xFF03);
+        assert_eq!((register.readable, register.writable), (0x00, 0x00));
+    }
+
+    #[test]
+    fn test_cgb_registers_are_unused_on_dmg() {
+        assert_eq!(lookup(0xFF70, Model::CGB).name, "SVBK");
+        assert_eq!(lookup(0xFF70, Model::DMG).name, "-");
+        assert_eq!(lookup(0xFF40, Model::DMG).name, "LCDC");
+    }
+
+    #[test]
+    fn test_every_cgb_register_is_listed() {
+        for (address, name) in [
+            (0xFF4C, "KEY0"),
+            (0xFF72, "FF72"),
+            (0xFF73, "FF73"),
+            (0xFF74, "FF74"),
+            (0xFF77, "PCM34"),
+        ] {
+            assert_eq!(lookup(address, Model::CGB).name, name);
+            assert_eq!(lookup(address, Model::DMG).name, "-");
+        }
+    }
+
+    #[test]
+    fn test_sc_clock_speed_bit_only_exists_on_cgb() {
+        assert_eq!(lookup(0xFF02, Model::DMG).readable, 0x81);
+        assert_eq!(lookup(0xFF02, Model::SGB2).readable, 0x81);
+        assert_eq!(lookup(0xFF02, Model::CGB).readable, 0x83);
+        assert_eq!(lookup(0xFF02, Model::AGB).writable, 0x83);
+    }
+}
@@ -0,0 +1,115 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+const SELECT_DIRECTIONS: u8 = 1 << 4;
+const SELECT_BUTTONS: u8 = 1 << 5;
+
+/// The P1/JOYP register. Lines are active low: a pressed button in a selected group
+/// reads back as 0.
+pub struct Joypad {
+    select: u8,
+    directions: u8,
+    buttons: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: SELECT_DIRECTIONS | SELECT_BUTTONS,
+            directions: 0,
+            buttons: 0,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | (!self.pressed_lines() & 0x0F)
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & (SELECT_DIRECTIONS | SELECT_BUTTONS);
+    }
+
+    /// Updates a button and returns true when one of the selected input lines went low,
+    /// which is what raises the joypad interrupt.
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> bool {
+        let before = self.pressed_lines();
+
+        let (group, bit) = match button {
+            Button::Right => (&mut self.directions, 0),
+            Button::Left => (&mut self.directions, 1),
+            Button::Up => (&mut self.directions, 2),
+            Button::Down => (&mut self.directions, 3),
+            Button::A => (&mut self.buttons, 0),
+            Button::B => (&mut self.buttons, 1),
+            Button::Select => (&mut self.buttons, 2),
+            Button::Start => (&mut self.buttons, 3),
+        };
+
+        if pressed {
+            *group |= 1 << bit;
+        } else {
+            *group &= !(1 << bit);
+        }
+
+        self.pressed_lines() & !before != 0
+    }
+
+    fn pressed_lines(&self) -> u8 {
+        let mut lines = 0;
+
+        if self.select & SELECT_DIRECTIONS == 0 {
+            lines |= self.directions;
+        }
+
+        if self.select & SELECT_BUTTONS == 0 {
+            lines |= self.buttons;
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nothing_selected_reads_high() {
+        let mut joypad = Joypad::new();
+        joypad.set_button(Button::A, true);
+
+        assert_eq!(joypad.read(), 0xFF);
+    }
+
+    #[test]
+    fn test_selected_group_reads_low() {
+        let mut joypad = Joypad::new();
+        joypad.set_button(Button::Start, true);
+        joypad.set_button(Button::Left, true);
+
+        joypad.write(0x10);
+        assert_eq!(joypad.read(), 0xD7);
+
+        joypad.write(0x20);
+        assert_eq!(joypad.read(), 0xED);
+    }
+
+    #[test]
+    fn test_press_on_selected_line_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        joypad.write(0x20);
+
+        assert!(!joypad.set_button(Button::A, true));
+        assert!(joypad.set_button(Button::Down, true));
+        assert!(!joypad.set_button(Button::Down, false));
+    }
+}
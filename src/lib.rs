@@ -0,0 +1,30 @@
+#![allow(dead_code)]
+#![allow(unused_variables)]
+#![allow(clippy::upper_case_acronyms)]
+
+mod boot;
+mod bus;
+mod cartridge;
+mod cpu;
+mod dma;
+mod emu;
+mod interrupts;
+mod io;
+mod joypad;
+mod memory;
+mod model;
+mod ppu;
+mod serial;
+mod tpu;
+mod trace;
+
+pub use boot::BootRomError;
+pub use cartridge::{CartridgeError, Header};
+pub use cpu::disassembler::Disassembly;
+pub use cpu::Registers;
+pub use dma::DmaStatus;
+pub use emu::{Emulator, Event};
+pub use joypad::Button;
+pub use model::Model;
+pub use ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use trace::{Tracer, Trigger};
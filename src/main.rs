@@ -1,17 +1,95 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
+use rustboy::{Emulator, Event, Model, Tracer};
+use std::fs::File;
+use std::io::{self, Write};
+use std::{env, process};
 
-use crate::emu::EMU;
+fn main() {
+    let usage = "Usage: rustboy <rom> [--model <model>] [--boot <file>] [--trace <file>]";
+    let mut args = env::args().skip(1);
+    let Some(rom) = args.next() else {
+        eprintln!("{}", usage);
+        process::exit(2);
+    };
+    let mut model = Model::DMG;
+    let mut boot = None;
+    let mut trace = None;
+    while let Some(flag) = args.next() {
+        match (flag.as_str(), args.next()) {
+            ("--model", Some(name)) => {
+                match Model::ALL
+                    .into_iter()
+                    .find(|model| model.to_string().eq_ignore_ascii_case(&name))
+                {
+                    Some(found) => model = found,
+                    None => {
+                        eprintln!("unknown model {}, expected one of {:?}", name, Model::ALL);
+                        process::exit(2);
+                    }
+                }
+            }
+            ("--boot", Some(file)) => boot = Some(file),
+            ("--trace", Some(file)) => trace = Some(file),
+            _ => {
+                eprintln!("{}", usage);
+                process::exit(2);
+            }
+        }
+    }
 
-mod bus;
-mod cartridge;
-mod cpu;
-mod emu;
-mod interrupts;
-mod ppu;
-pub mod tpu;
+    let mut emulation = match Emulator::from(&rom, model) {
+        Ok(emulation) => emulation,
+        Err(error) => {
+            eprintln!("{}: {}", rom, error);
+            process::exit(1);
+        }
+    };
 
-fn main() {
-    let mut emulation = EMU::test(1);
-    emulation.run();
+    println!("Cartridge Loaded...");
+    println!("    File     : {}", emulation.cartridge_name());
+    println!("{}", emulation.cartridge_header());
+
+    if let Some(file) = boot {
+        if let Err(error) = emulation.load_boot_rom(&file) {
+            eprintln!("{}: {}", file, error);
+            process::exit(1);
+        }
+    }
+
+    if let Some(file) = trace {
+        match File::create(&file) {
+            Ok(writer) => {
+                emulation.set_tracer(Some(Tracer::new(writer)));
+            }
+            Err(error) => {
+                eprintln!("{}: {}", file, error);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut printed = 0;
+    loop {
+        emulation.run_frame();
+
+        // Test ROMs report their results over the link port.
+        let output = emulation.serial_output();
+        if output.len() > printed {
+            print!("{}", String::from_utf8_lossy(&output[printed..]));
+            io::stdout().flush().ok();
+            printed = output.len();
+        }
+
+        if let Some(Event::IllegalOpcode { pc, opcode, bank }) = emulation.poll_event() {
+            eprintln!(
+                "Illegal opcode {:#04X} at {:02X}:{:04X}, the CPU has locked up",
+                opcode, bank, pc
+            );
+            process::exit(1);
+        }
+
+        if emulation.is_stopped() {
+            eprintln!("The CPU executed STOP and there is no joypad to wake it");
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,22 @@
+use crate::interrupts::Interrupts;
+
+/// Everything the CPU can see of the rest of the system: a 16-bit address space and a
+/// clock. `Bus` is the real implementation, tests can plug in flat memory instead.
+pub trait Memory {
+    fn read(&mut self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Reads without side effects or timing, for logging and debugging.
+    fn peek(&self, address: u16) -> u8;
+
+    /// Advances the rest of the system by `cycles` M-cycles.
+    fn tick(&mut self, cycles: u8);
+
+    /// Called by STOP. Resets DIV, and on CGB performs an armed speed switch, returning
+    /// true when it did so instead of entering low power.
+    fn stop(&mut self) -> bool;
+
+    /// IF and IE, which the CPU checks and acknowledges without a bus access.
+    fn interrupts(&mut self) -> &mut Interrupts;
+}
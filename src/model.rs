@@ -0,0 +1,105 @@
+use crate::cpu::Registers;
+use std::fmt::{Display, Formatter};
+
+/// The Game Boy hardware being emulated. Games tell them apart by the registers the boot
+/// ROM leaves behind, so each model starts with its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model {
+    /// The earliest DMG, with a different boot ROM.
+    DMG0,
+    DMG,
+    /// Game Boy Pocket and Light.
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    /// A Game Boy Advance running a CGB game.
+    AGB,
+}
+
+impl Model {
+    pub const ALL: [Model; 7] = [
+        Model::DMG0,
+        Model::DMG,
+        Model::MGB,
+        Model::SGB,
+        Model::SGB2,
+        Model::CGB,
+        Model::AGB,
+    ];
+
+    /// Whether the CGB registers, double speed and CGB memory behaviour exist.
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::CGB | Model::AGB)
+    }
+
+    pub fn is_sgb(self) -> bool {
+        matches!(self, Model::SGB | Model::SGB2)
+    }
+
+    /// The CPU registers at 0x0100 once the boot ROM is done. CGB and AGB values are
+    /// those left for a CGB game.
+    pub(crate) fn registers(self) -> Registers {
+        let [a, f, b, c, d, e, h, l] = match self {
+            Model::DMG0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            Model::DMG => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::MGB => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::SGB => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::SGB2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::CGB => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            Model::AGB => [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        };
+
+        Registers {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            sp: 0xFFFE,
+            pc: 0x0100,
+        }
+    }
+
+    /// The internal 16-bit divider once the boot ROM is done. On SGB and CGB it depends
+    /// on how long the boot ROM ran, these are typical values.
+    pub(crate) fn div(self) -> u16 {
+        match self {
+            Model::DMG0 => 0x182C,
+            Model::DMG | Model::MGB => 0xABCC,
+            Model::SGB | Model::SGB2 => 0xD85C,
+            Model::CGB | Model::AGB => 0x1EA0,
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_models_are_told_apart_by_their_registers() {
+        for (i, first) in Model::ALL.iter().enumerate() {
+            for second in &Model::ALL[i + 1..] {
+                assert_ne!(
+                    first.registers(),
+                    second.registers(),
+                    "{} {}",
+                    first,
+                    second
+                );
+            }
+        }
+        assert_eq!(Model::CGB.registers().a, 0x11);
+        assert_eq!(Model::AGB.registers().b & 1, 1);
+    }
+}
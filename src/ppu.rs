@@ -1 +1,927 @@
-pub struct PPU;
+use crate::interrupts;
+use fifo::{FetchStep, Fetcher, ObjPixel, PixelFifo, FIFO_DEPTH};
+
+mod fifo;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+const OBJ_FETCH_DOTS: u8 = 6;
+const V_BLANK_LINE: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const LCD_ENABLE: u8 = 1 << 7;
+const WINDOW_TILE_MAP: u8 = 1 << 6;
+const WINDOW_ENABLE: u8 = 1 << 5;
+const TILE_DATA_UNSIGNED: u8 = 1 << 4;
+const BG_TILE_MAP: u8 = 1 << 3;
+const OBJ_TALL: u8 = 1 << 2;
+const OBJ_ENABLE: u8 = 1 << 1;
+const BG_WINDOW_ENABLE: u8 = 1;
+
+const OBJ_BG_PRIORITY: u8 = 1 << 7;
+const OBJ_Y_FLIP: u8 = 1 << 6;
+const OBJ_X_FLIP: u8 = 1 << 5;
+const OBJ_PALETTE: u8 = 1 << 4;
+const OBJS_PER_LINE: usize = 10;
+
+const STAT_LYC_FLAG: u8 = 1 << 2;
+const STAT_HBLANK_SOURCE: u8 = 1 << 3;
+const STAT_VBLANK_SOURCE: u8 = 1 << 4;
+const STAT_OAM_SOURCE: u8 = 1 << 5;
+const STAT_LYC_SOURCE: u8 = 1 << 6;
+const STAT_WRITABLE: u8 = 0b0111_1000;
+
+#[derive(Clone, Copy)]
+struct Sprite {
+    y: u8,
+    x: u8,
+    tile: u8,
+    flags: u8,
+    index: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OAMScan = 2,
+    PixelTransfer = 3,
+}
+
+pub struct PPU {
+    vram: Vec<u8>,
+    oam: Vec<u8>,
+    framebuffer: Vec<u8>,
+    line_sprites: Vec<Sprite>,
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    wy: u8,
+    wx: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    mode: Mode,
+    dot: u16,
+    stat_line: bool,
+    fetcher: Fetcher,
+    fifo: PixelFifo,
+    lx: u8,
+    discard: u8,
+    next_sprite: usize,
+    sprite_fetch: Option<u8>,
+    window_line: u8,
+    wy_triggered: bool,
+    window_drawn: bool,
+    frames: u64,
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        Self {
+            vram: vec![0; 0x2000],
+            oam: vec![0; 0xA0],
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            line_sprites: Vec::with_capacity(OBJS_PER_LINE),
+            lcdc: 0x91,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            wy: 0,
+            wx: 0,
+            bgp: 0xFC,
+            obp0: 0xFF,
+            obp1: 0xFF,
+            mode: Mode::OAMScan,
+            dot: 0,
+            stat_line: false,
+            fetcher: Fetcher::new(),
+            fifo: PixelFifo::new(),
+            lx: 0,
+            discard: 0,
+            next_sprite: 0,
+            sprite_fetch: None,
+            window_line: 0,
+            wy_triggered: false,
+            window_drawn: false,
+            frames: 0,
+        }
+    }
+
+    /// Advances the PPU by a single dot and returns the interrupt flags it raised.
+    pub fn tick(&mut self) -> u8 {
+        if !self.lcd_enabled() {
+            return 0;
+        }
+
+        let mut requested = 0;
+
+        if self.dot == 0 && self.ly < V_BLANK_LINE && self.ly == self.wy {
+            self.wy_triggered = true;
+        }
+
+        self.dot += 1;
+
+        match self.mode {
+            Mode::OAMScan if self.dot == OAM_SCAN_DOTS => {
+                self.scan_oam();
+                self.start_pixel_transfer();
+            }
+            Mode::PixelTransfer => self.transfer_dot(),
+            _ => {}
+        }
+
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+            self.ly += 1;
+
+            if self.ly == V_BLANK_LINE {
+                self.mode = Mode::VBlank;
+                self.frames += 1;
+                requested |= interrupts::V_BLANK;
+            } else if self.ly == LINES_PER_FRAME {
+                self.ly = 0;
+                self.window_line = 0;
+                self.wy_triggered = false;
+                self.mode = Mode::OAMScan;
+            } else if self.ly < V_BLANK_LINE {
+                self.mode = Mode::OAMScan;
+            }
+        }
+
+        requested | self.update_stat_line()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The number of frames completed since power on.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The last rendered frame as shades 0 (lightest) to 3 (darkest), one byte per pixel.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    pub fn vram_read(&self, address: u16) -> u8 {
+        self.vram[(address - 0x8000) as usize]
+    }
+
+    pub fn vram_write(&mut self, address: u16, value: u8) {
+        self.vram[(address - 0x8000) as usize] = value;
+    }
+
+    /// Whether the PPU is using OAM, which hides it from the CPU.
+    pub fn oam_blocked(&self) -> bool {
+        self.lcd_enabled() && matches!(self.mode, Mode::OAMScan | Mode::PixelTransfer)
+    }
+
+    /// The DMG OAM bug. A CPU access to 0xFEA0-0xFEFF during mode 2 garbles the 8 byte
+    /// row the PPU is scanning by mixing it with the row before. The first row is safe.
+    /// Accesses to OAM proper, and 16-bit increments landing in OAM, trip it on hardware
+    /// too, but aren't modelled yet.
+    pub fn corrupt_oam(&mut self, write: bool) {
+        if !self.lcd_enabled() || self.mode != Mode::OAMScan {
+            return;
+        }
+
+        let row = (self.dot / 4) as usize * 8;
+        if row == 0 || row >= self.oam.len() {
+            return;
+        }
+
+        let previous = row - 8;
+        for byte in 0..2 {
+            let a = self.oam[row + byte];
+            let b = self.oam[previous + byte];
+            let c = self.oam[previous + 4 + byte];
+
+            self.oam[row + byte] = if write {
+                ((a ^ c) & (b ^ c)) ^ c
+            } else {
+                b | (a & c)
+            };
+        }
+        self.oam.copy_within(previous + 2..row, row + 2);
+    }
+
+    pub fn oam_read(&self, address: u16) -> u8 {
+        self.oam[(address - 0xFE00) as usize]
+    }
+
+    pub fn oam_write(&mut self, address: u16, value: u8) {
+        self.oam[(address - 0xFE00) as usize] = value;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcdc,
+            0xFF41 => self.read_stat(),
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF40 => self.write_lcdc(value),
+            0xFF41 => self.stat = value & STAT_WRITABLE,
+            0xFF42 => self.scy = value,
+            0xFF43 => self.scx = value,
+            0xFF44 => {}
+            0xFF45 => self.lyc = value,
+            0xFF47 => self.bgp = value,
+            0xFF48 => self.obp0 = value,
+            0xFF49 => self.obp1 = value,
+            0xFF4A => self.wy = value,
+            0xFF4B => self.wx = value,
+            _ => {}
+        }
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCD_ENABLE != 0
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = value;
+
+        if was_enabled && !self.lcd_enabled() {
+            self.ly = 0;
+            self.dot = 0;
+            self.window_line = 0;
+            self.wy_triggered = false;
+            self.mode = Mode::HBlank;
+            self.stat_line = false;
+        } else if !was_enabled && self.lcd_enabled() {
+            self.mode = Mode::OAMScan;
+        }
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcdc & OBJ_TALL != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Picks the first ten objects in OAM order that overlap the current line, then sorts
+    /// them into DMG drawing priority: lowest X first, with OAM index breaking ties.
+    fn scan_oam(&mut self) {
+        let height = self.sprite_height() as i16;
+        let line = self.ly as i16;
+        self.line_sprites.clear();
+
+        for (index, entry) in self.oam.chunks_exact(4).enumerate() {
+            let top = entry[0] as i16 - 16;
+            if line < top || line >= top + height {
+                continue;
+            }
+
+            self.line_sprites.push(Sprite {
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                flags: entry[3],
+                index: index as u8,
+            });
+
+            if self.line_sprites.len() == OBJS_PER_LINE {
+                break;
+            }
+        }
+
+        self.line_sprites
+            .sort_by_key(|sprite| (sprite.x, sprite.index));
+    }
+
+    fn start_pixel_transfer(&mut self) {
+        self.mode = Mode::PixelTransfer;
+        self.fetcher = Fetcher::new();
+        self.fifo.clear();
+        self.lx = 0;
+        self.discard = self.scx % 8;
+        self.next_sprite = 0;
+        self.sprite_fetch = None;
+        self.window_drawn = false;
+    }
+
+    /// Runs one dot of mode 3. Every register is sampled at the moment the hardware would
+    /// read it, so writes made part way through a line take effect from the next pixel.
+    fn transfer_dot(&mut self) {
+        if let Some(dots) = self.sprite_fetch {
+            if dots + 1 == OBJ_FETCH_DOTS {
+                self.fetch_sprite();
+            } else {
+                self.sprite_fetch = Some(dots + 1);
+            }
+            return;
+        }
+
+        if self.sprite_pending() {
+            if self.fetcher.step == FetchStep::Push {
+                self.sprite_fetch = Some(0);
+            } else {
+                self.fetcher_dot();
+            }
+            return;
+        }
+
+        if self.window_should_start() {
+            self.fetcher.start_window();
+            self.fifo.background.clear();
+            self.window_drawn = true;
+            if self.wx < 7 {
+                self.discard = 7 - self.wx;
+            }
+        }
+
+        self.fetcher_dot();
+        self.shift_pixel();
+    }
+
+    fn sprite_pending(&self) -> bool {
+        self.lcdc & OBJ_ENABLE != 0
+            && self
+                .line_sprites
+                .get(self.next_sprite)
+                .is_some_and(|sprite| sprite.x <= self.lx + 8)
+    }
+
+    fn window_should_start(&self) -> bool {
+        !self.fetcher.window
+            && self.wy_triggered
+            && self.lcdc & WINDOW_ENABLE != 0
+            && self.lx as u16 + 7 >= self.wx as u16
+    }
+
+    fn fetcher_dot(&mut self) {
+        if self.fetcher.step == FetchStep::Push {
+            if self.fifo.background.is_empty() {
+                self.fifo.push_background(self.fetcher.lo, self.fetcher.hi);
+                self.fetcher.tile_x = self.fetcher.tile_x.wrapping_add(1);
+                self.fetcher.step = FetchStep::Tile;
+            }
+            return;
+        }
+
+        self.fetcher.dots += 1;
+        if self.fetcher.dots < 2 {
+            return;
+        }
+        self.fetcher.dots = 0;
+
+        match self.fetcher.step {
+            FetchStep::Tile => {
+                self.fetcher.tile_index = self.vram_read(self.tile_map_address());
+                self.fetcher.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                self.fetcher.lo = self.vram_read(self.tile_data_address());
+                self.fetcher.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                self.fetcher.hi = self.vram_read(self.tile_data_address() + 1);
+                // The first fetch of every line is thrown away and repeated
+                if self.fetcher.first_fetch {
+                    self.fetcher.first_fetch = false;
+                    self.fetcher.step = FetchStep::Tile;
+                } else {
+                    self.fetcher.step = FetchStep::Push;
+                }
+            }
+            FetchStep::Push => {}
+        }
+    }
+
+    fn tile_map_address(&self) -> u16 {
+        if self.fetcher.window {
+            let map = if self.lcdc & WINDOW_TILE_MAP != 0 {
+                0x9C00
+            } else {
+                0x9800
+            };
+            map + (self.window_line as u16 / 8) * 32 + (self.fetcher.tile_x as u16 & 31)
+        } else {
+            let map = if self.lcdc & BG_TILE_MAP != 0 {
+                0x9C00
+            } else {
+                0x9800
+            };
+            let y = self.ly.wrapping_add(self.scy) as u16;
+            let x = (self.scx / 8).wrapping_add(self.fetcher.tile_x) as u16 & 31;
+            map + (y / 8) * 32 + x
+        }
+    }
+
+    fn tile_data_address(&self) -> u16 {
+        let row = if self.fetcher.window {
+            self.window_line % 8
+        } else {
+            self.ly.wrapping_add(self.scy) % 8
+        } as u16;
+        let tile_index = self.fetcher.tile_index;
+
+        if self.lcdc & TILE_DATA_UNSIGNED != 0 {
+            0x8000 + tile_index as u16 * 16 + row * 2
+        } else {
+            (0x9000 + (tile_index as i8 as i32) * 16) as u16 + row * 2
+        }
+    }
+
+    /// Fetches the pending object's row and mixes it into the object FIFO, dropping any
+    /// columns that hang off the left edge of the screen.
+    fn fetch_sprite(&mut self) {
+        let sprite = self.line_sprites[self.next_sprite];
+        self.next_sprite += 1;
+        self.sprite_fetch = None;
+
+        let height = self.sprite_height();
+        let mut row = self.ly.wrapping_add(16).wrapping_sub(sprite.y) % height;
+        if sprite.flags & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        let address = 0x8000 + tile as u16 * 16 + row as u16 * 2;
+        let lo = self.vram_read(address);
+        let hi = self.vram_read(address + 1);
+
+        let skipped = (self.lx + 8 - sprite.x) as usize;
+        let mut pixels = [ObjPixel::default(); FIFO_DEPTH];
+        for (slot, column) in pixels.iter_mut().zip(skipped..FIFO_DEPTH) {
+            let bit = if sprite.flags & OBJ_X_FLIP != 0 {
+                column
+            } else {
+                7 - column
+            };
+            *slot = ObjPixel {
+                color: (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1),
+                palette: (sprite.flags & OBJ_PALETTE != 0) as u8,
+                bg_priority: sprite.flags & OBJ_BG_PRIORITY != 0,
+            };
+        }
+
+        self.fifo.merge_objects(pixels);
+    }
+
+    fn shift_pixel(&mut self) {
+        let Some(color) = self.fifo.background.pop_front() else {
+            return;
+        };
+
+        if self.discard > 0 {
+            self.discard -= 1;
+            return;
+        }
+
+        let color = if self.lcdc & BG_WINDOW_ENABLE != 0 {
+            color
+        } else {
+            0
+        };
+        let mut shade = palette_shade(self.bgp, color);
+
+        if let Some(pixel) = self.fifo.objects.pop_front() {
+            let visible = pixel.color != 0
+                && self.lcdc & OBJ_ENABLE != 0
+                && (!pixel.bg_priority || color == 0);
+            if visible {
+                let palette = if pixel.palette != 0 {
+                    self.obp1
+                } else {
+                    self.obp0
+                };
+                shade = palette_shade(palette, pixel.color);
+            }
+        }
+
+        self.framebuffer[self.ly as usize * SCREEN_WIDTH + self.lx as usize] = shade;
+        self.lx += 1;
+
+        if self.lx as usize == SCREEN_WIDTH {
+            self.mode = Mode::HBlank;
+            if self.window_drawn {
+                self.window_line += 1;
+            }
+        }
+    }
+
+    fn read_stat(&self) -> u8 {
+        let mut stat = 0x80 | self.stat;
+
+        if self.ly == self.lyc {
+            stat |= STAT_LYC_FLAG;
+        }
+
+        if self.lcd_enabled() {
+            stat |= self.mode as u8;
+        }
+
+        stat
+    }
+
+    /// The STAT interrupt only fires on a rising edge of the OR of every enabled source,
+    /// so overlapping sources block each other ("STAT blocking").
+    fn update_stat_line(&mut self) -> u8 {
+        let line = (self.stat & STAT_LYC_SOURCE != 0 && self.ly == self.lyc)
+            || match self.mode {
+                Mode::HBlank => self.stat & STAT_HBLANK_SOURCE != 0,
+                Mode::VBlank => self.stat & STAT_VBLANK_SOURCE != 0,
+                Mode::OAMScan => self.stat & STAT_OAM_SOURCE != 0,
+                Mode::PixelTransfer => false,
+            };
+
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+
+        if rising {
+            interrupts::LCD_STAT
+        } else {
+            0
+        }
+    }
+}
+
+fn palette_shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0b11
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(ppu: &mut PPU, dots: u32) -> u8 {
+        let mut requested = 0;
+        for _ in 0..dots {
+            requested |= ppu.tick();
+        }
+        requested
+    }
+
+    #[test]
+    fn test_mode_sequence_for_a_visible_line() {
+        let mut ppu = PPU::new();
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+
+        run(&mut ppu, 80);
+        assert_eq!(ppu.mode(), Mode::PixelTransfer);
+
+        run(&mut ppu, 172);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        run(&mut ppu, 204);
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+        assert_eq!(ppu.read(0xFF44), 1);
+    }
+
+    #[test]
+    fn test_v_blank_interrupt_and_frame_wrap() {
+        let mut ppu = PPU::new();
+
+        let requested = run(&mut ppu, 456 * 144);
+        assert_eq!(requested & interrupts::V_BLANK, interrupts::V_BLANK);
+        assert_eq!(ppu.mode(), Mode::VBlank);
+        assert_eq!(ppu.read(0xFF44), 144);
+
+        run(&mut ppu, 456 * 10);
+        assert_eq!(ppu.mode(), Mode::OAMScan);
+        assert_eq!(ppu.read(0xFF44), 0);
+    }
+
+    #[test]
+    fn test_lyc_coincidence_raises_stat() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF45, 2);
+        ppu.write(0xFF41, STAT_LYC_SOURCE);
+
+        assert_eq!(run(&mut ppu, 456) & interrupts::LCD_STAT, 0);
+        assert_eq!(ppu.read(0xFF41) & STAT_LYC_FLAG, 0);
+
+        assert_eq!(
+            run(&mut ppu, 456) & interrupts::LCD_STAT,
+            interrupts::LCD_STAT
+        );
+        assert_eq!(ppu.read(0xFF41) & STAT_LYC_FLAG, STAT_LYC_FLAG);
+    }
+
+    #[test]
+    fn test_stat_blocking() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF41, STAT_HBLANK_SOURCE | STAT_OAM_SOURCE);
+
+        run(&mut ppu, 252);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        // HBlank hands straight over to OAM scan, so the line never drops
+        assert_eq!(run(&mut ppu, 204) & interrupts::LCD_STAT, 0);
+    }
+
+    fn write_tile(ppu: &mut PPU, address: u16, lo: u8, hi: u8) {
+        for row in 0..8 {
+            ppu.vram_write(address + row * 2, lo);
+            ppu.vram_write(address + row * 2 + 1, hi);
+        }
+    }
+
+    #[test]
+    fn test_palette_shade() {
+        assert_eq!(palette_shade(0xE4, 0), 0);
+        assert_eq!(palette_shade(0xE4, 3), 3);
+        assert_eq!(palette_shade(0x1B, 0), 3);
+        assert_eq!(palette_shade(0x1B, 2), 1);
+    }
+
+    #[test]
+    fn test_background_renders_through_bgp() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF47, 0xE4);
+        // Tile 1 is solid colour 3, placed at the second map entry
+        write_tile(&mut ppu, 0x8010, 0xFF, 0xFF);
+        ppu.vram_write(0x9801, 1);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert!(line[..8].iter().all(|&shade| shade == 0));
+        assert!(line[8..16].iter().all(|&shade| shade == 3));
+        assert!(line[16..].iter().all(|&shade| shade == 0));
+    }
+
+    #[test]
+    fn test_background_scroll() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF47, 0xE4);
+        write_tile(&mut ppu, 0x8010, 0xFF, 0x00);
+        ppu.vram_write(0x9801, 1);
+        ppu.write(0xFF43, 4);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert_eq!(line[3], 0);
+        assert_eq!(line[4], 1);
+        assert_eq!(line[11], 1);
+        assert_eq!(line[12], 0);
+    }
+
+    #[test]
+    fn test_signed_tile_addressing() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF40, 0x81);
+        ppu.write(0xFF47, 0xE4);
+        // Index 0xFF addresses the tile just below 0x9000
+        write_tile(&mut ppu, 0x8FF0, 0x00, 0xFF);
+        ppu.vram_write(0x9800, 0xFF);
+
+        run(&mut ppu, 456);
+        assert_eq!(ppu.framebuffer()[0], 2);
+        assert_eq!(ppu.framebuffer()[8], 0);
+    }
+
+    #[test]
+    fn test_window_overlays_background() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF40, 0xF1);
+        ppu.write(0xFF47, 0xE4);
+        write_tile(&mut ppu, 0x8010, 0xFF, 0xFF);
+        for entry in 0..32 {
+            ppu.vram_write(0x9C00 + entry, 1);
+        }
+        ppu.write(0xFF4A, 1);
+        ppu.write(0xFF4B, 87);
+
+        run(&mut ppu, 456);
+        assert!(ppu.framebuffer()[..SCREEN_WIDTH]
+            .iter()
+            .all(|&shade| shade == 0));
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[SCREEN_WIDTH..SCREEN_WIDTH * 2];
+        assert!(line[..80].iter().all(|&shade| shade == 0));
+        assert!(line[80..].iter().all(|&shade| shade == 3));
+    }
+
+    fn write_sprite(ppu: &mut PPU, index: u16, y: u8, x: u8, tile: u8, flags: u8) {
+        let address = 0xFE00 + index * 4;
+        ppu.oam_write(address, y);
+        ppu.oam_write(address + 1, x);
+        ppu.oam_write(address + 2, tile);
+        ppu.oam_write(address + 3, flags);
+    }
+
+    fn sprite_ppu() -> PPU {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF40, 0x93);
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF48, 0xE4);
+        ppu.write(0xFF49, 0x1B);
+        ppu
+    }
+
+    #[test]
+    fn test_sprite_draws_with_its_palette() {
+        let mut ppu = sprite_ppu();
+        write_tile(&mut ppu, 0x8010, 0xFF, 0x00);
+        write_sprite(&mut ppu, 0, 16, 8, 1, 0);
+        write_sprite(&mut ppu, 1, 16, 16, 1, OBJ_PALETTE);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert!(line[..8].iter().all(|&shade| shade == 1));
+        assert!(line[8..16].iter().all(|&shade| shade == 2));
+        assert_eq!(line[16], 0);
+    }
+
+    #[test]
+    fn test_sprite_x_flip_and_y_flip() {
+        let mut ppu = sprite_ppu();
+        // Only the top-left pixel of tile 1 is set
+        ppu.vram_write(0x8010, 0x80);
+        write_sprite(&mut ppu, 0, 16, 8, 1, OBJ_X_FLIP);
+        write_sprite(&mut ppu, 1, 9, 16, 1, OBJ_Y_FLIP);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert_eq!(line[0], 0);
+        assert_eq!(line[7], 1);
+        assert_eq!(line[8], 1);
+    }
+
+    #[test]
+    fn test_tall_sprites_use_both_tiles() {
+        let mut ppu = sprite_ppu();
+        ppu.write(0xFF40, 0x97);
+        write_tile(&mut ppu, 0x8020, 0xFF, 0x00);
+        write_tile(&mut ppu, 0x8030, 0x00, 0xFF);
+        // Bit 0 of the tile index is ignored in 8x16 mode
+        write_sprite(&mut ppu, 0, 16, 8, 3, 0);
+
+        run(&mut ppu, 456 * 9);
+        assert_eq!(ppu.framebuffer()[0], 1);
+        assert_eq!(ppu.framebuffer()[SCREEN_WIDTH * 8], 2);
+    }
+
+    #[test]
+    fn test_ten_sprites_per_line() {
+        let mut ppu = sprite_ppu();
+        write_tile(&mut ppu, 0x8010, 0xFF, 0x00);
+        for index in 0..11 {
+            write_sprite(&mut ppu, index, 16, 8 + index as u8 * 8, 1, 0);
+        }
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert_eq!(line[9 * 8], 1);
+        assert_eq!(line[10 * 8], 0);
+    }
+
+    #[test]
+    fn test_sprite_priority_by_x_then_oam_index() {
+        let mut ppu = sprite_ppu();
+        write_tile(&mut ppu, 0x8010, 0xFF, 0x00);
+        write_tile(&mut ppu, 0x8020, 0x00, 0xFF);
+        write_sprite(&mut ppu, 0, 16, 12, 1, 0);
+        write_sprite(&mut ppu, 1, 16, 8, 2, 0);
+        write_sprite(&mut ppu, 2, 16, 30, 2, 0);
+        write_sprite(&mut ppu, 3, 16, 30, 1, 0);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        // Lower X wins where the objects overlap
+        assert_eq!(line[4], 2);
+        assert_eq!(line[8], 1);
+        // Equal X falls back to the lower OAM index
+        assert_eq!(line[22], 2);
+    }
+
+    #[test]
+    fn test_sprite_behind_background() {
+        let mut ppu = sprite_ppu();
+        write_tile(&mut ppu, 0x8010, 0xFF, 0xFF);
+        write_tile(&mut ppu, 0x8020, 0x0F, 0x00);
+        ppu.vram_write(0x9800, 2);
+        ppu.vram_write(0x9801, 2);
+        write_sprite(&mut ppu, 0, 16, 8, 1, OBJ_BG_PRIORITY);
+        write_sprite(&mut ppu, 1, 16, 16, 1, 0);
+
+        run(&mut ppu, 456);
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        // Background colour 0 never hides an object
+        assert_eq!(line[0], 3);
+        assert_eq!(line[4], 1);
+        assert_eq!(line[8], 3);
+        assert_eq!(line[12], 3);
+    }
+
+    fn pixel_transfer_length(ppu: &mut PPU) -> u32 {
+        while ppu.mode() != Mode::PixelTransfer {
+            ppu.tick();
+        }
+
+        let mut dots = 0;
+        while ppu.mode() == Mode::PixelTransfer {
+            ppu.tick();
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn test_pixel_transfer_length() {
+        let mut ppu = PPU::new();
+        assert_eq!(pixel_transfer_length(&mut ppu), 172);
+
+        ppu.write(0xFF43, 3);
+        assert_eq!(pixel_transfer_length(&mut ppu), 175);
+    }
+
+    #[test]
+    fn test_window_and_sprites_extend_pixel_transfer() {
+        let mut ppu = sprite_ppu();
+        ppu.write(0xFF40, 0xB3);
+        ppu.write(0xFF4B, 87);
+        assert_eq!(pixel_transfer_length(&mut ppu), 178);
+
+        let mut ppu = sprite_ppu();
+        write_sprite(&mut ppu, 0, 16, 80, 1, 0);
+        let length = pixel_transfer_length(&mut ppu);
+        assert!((178..=183).contains(&length), "mode 3 took {} dots", length);
+    }
+
+    #[test]
+    fn test_mid_line_palette_write() {
+        let mut ppu = PPU::new();
+        write_tile(&mut ppu, 0x8000, 0xFF, 0xFF);
+        ppu.write(0xFF47, 0x00);
+
+        // Mode 3 starts at dot 80 and the first pixel leaves the FIFO 13 dots later
+        run(&mut ppu, 80 + 12 + 40);
+        ppu.write(0xFF47, 0xFF);
+        run(&mut ppu, 456 - 132);
+
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert!(line[..40].iter().all(|&shade| shade == 0));
+        assert!(line[40..].iter().all(|&shade| shade == 3));
+    }
+
+    #[test]
+    fn test_window_line_counter_skips_hidden_lines() {
+        let mut ppu = PPU::new();
+        ppu.write(0xFF40, 0xF1);
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF4B, 7);
+        // Window row 0 is colour 1, row 1 is colour 2
+        write_tile(&mut ppu, 0x8010, 0xFF, 0x00);
+        ppu.vram_write(0x8012, 0x00);
+        ppu.vram_write(0x8013, 0xFF);
+        ppu.vram_write(0x9C00, 1);
+
+        run(&mut ppu, 456);
+        ppu.write(0xFF40, 0xD1);
+        run(&mut ppu, 456);
+        ppu.write(0xFF40, 0xF1);
+        run(&mut ppu, 456);
+
+        let frame = ppu.framebuffer();
+        assert_eq!(frame[0], 1);
+        assert_eq!(frame[SCREEN_WIDTH], 0);
+        assert_eq!(frame[SCREEN_WIDTH * 2], 2);
+    }
+
+    #[test]
+    fn test_lcd_off_resets_ly() {
+        let mut ppu = PPU::new();
+        run(&mut ppu, 456 * 3 + 10);
+        assert_eq!(ppu.read(0xFF44), 3);
+
+        ppu.write(0xFF40, 0x11);
+        assert_eq!(ppu.read(0xFF44), 0);
+        assert_eq!(ppu.read(0xFF41) & 0b11, 0);
+        assert_eq!(run(&mut ppu, 456), 0);
+        assert_eq!(ppu.read(0xFF44), 0);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+pub const FIFO_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FetchStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+/// The background/window tile fetcher. Each step but `Push` takes two dots, and `Push`
+/// waits until the background FIFO has drained before handing over eight new pixels.
+pub struct Fetcher {
+    pub step: FetchStep,
+    pub dots: u8,
+    pub tile_x: u8,
+    pub tile_index: u8,
+    pub lo: u8,
+    pub hi: u8,
+    pub window: bool,
+    pub first_fetch: bool,
+}
+
+impl Fetcher {
+    pub fn new() -> Self {
+        Self {
+            step: FetchStep::Tile,
+            dots: 0,
+            tile_x: 0,
+            tile_index: 0,
+            lo: 0,
+            hi: 0,
+            window: false,
+            first_fetch: true,
+        }
+    }
+
+    /// Restarts the fetcher at the first tile of the window.
+    pub fn start_window(&mut self) {
+        self.step = FetchStep::Tile;
+        self.dots = 0;
+        self.tile_x = 0;
+        self.window = true;
+        self.first_fetch = false;
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ObjPixel {
+    pub color: u8,
+    pub palette: u8,
+    pub bg_priority: bool,
+}
+
+pub struct PixelFifo {
+    pub background: VecDeque<u8>,
+    pub objects: VecDeque<ObjPixel>,
+}
+
+impl PixelFifo {
+    pub fn new() -> Self {
+        Self {
+            background: VecDeque::with_capacity(FIFO_DEPTH),
+            objects: VecDeque::with_capacity(FIFO_DEPTH),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.background.clear();
+        self.objects.clear();
+    }
+
+    pub fn push_background(&mut self, lo: u8, hi: u8) {
+        for bit in (0..8).rev() {
+            self.background
+                .push_back((((hi >> bit) & 1) << 1) | ((lo >> bit) & 1));
+        }
+    }
+
+    /// Mixes a fetched object row into the object FIFO. Pixels already in the FIFO came
+    /// from higher priority objects, so only their transparent slots are replaced.
+    pub fn merge_objects(&mut self, pixels: [ObjPixel; FIFO_DEPTH]) {
+        while self.objects.len() < FIFO_DEPTH {
+            self.objects.push_back(ObjPixel::default());
+        }
+
+        for (slot, pixel) in self.objects.iter_mut().zip(pixels) {
+            if slot.color == 0 {
+                *slot = pixel;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_background_is_msb_first() {
+        let mut fifo = PixelFifo::new();
+        fifo.push_background(0b1000_0001, 0b1100_0000);
+
+        let pixels: Vec<u8> = fifo.background.iter().copied().collect();
+        assert_eq!(pixels, vec![3, 2, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_merge_objects_keeps_opaque_pixels() {
+        let mut fifo = PixelFifo::new();
+        let first = ObjPixel {
+            color: 1,
+            palette: 0,
+            bg_priority: false,
+        };
+        let second = ObjPixel {
+            color: 2,
+            palette: 1,
+            bg_priority: true,
+        };
+
+        let mut row = [ObjPixel::default(); FIFO_DEPTH];
+        row[0] = first;
+        fifo.merge_objects(row);
+        fifo.merge_objects([second; FIFO_DEPTH]);
+
+        assert_eq!(fifo.objects[0], first);
+        assert!(fifo.objects.iter().skip(1).all(|&pixel| pixel == second));
+    }
+}
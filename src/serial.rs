@@ -0,0 +1,117 @@
+use crate::interrupts;
+
+/// Dots per bit with the internal 8192 Hz clock.
+const DOTS_PER_BIT: u16 = 512;
+
+const TRANSFER_START: u8 = 0x80;
+const INTERNAL_CLOCK: u8 = 0x01;
+/// CGB only, the bus hides it on other models.
+const CLOCK_SPEED: u8 = 0x02;
+
+/// The link port. Nothing is ever plugged in, so transfers shift in 0xFF. Every byte
+/// sent is kept, test ROMs report their results this way.
+pub struct Serial {
+    data: u8,
+    control: u8,
+    dots: u16,
+    bits: u8,
+    output: Vec<u8>,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            data: 0,
+            control: 0,
+            dots: 0,
+            bits: 0,
+            output: Vec::new(),
+        }
+    }
+
+    /// Advances a transfer by one dot and returns the interrupts it raised.
+    pub fn tick(&mut self) -> u8 {
+        if self.control & (TRANSFER_START | INTERNAL_CLOCK) != TRANSFER_START | INTERNAL_CLOCK {
+            return 0;
+        }
+
+        self.dots += 1;
+        if self.dots < DOTS_PER_BIT {
+            return 0;
+        }
+
+        self.dots = 0;
+        self.data = (self.data << 1) | 1;
+        self.bits += 1;
+
+        if self.bits < 8 {
+            return 0;
+        }
+
+        self.control &= !TRANSFER_START;
+        interrupts::SERIAL
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF01 => self.data = value,
+            0xFF02 => {
+                self.control = value & (TRANSFER_START | CLOCK_SPEED | INTERNAL_CLOCK);
+
+                if value & TRANSFER_START != 0 {
+                    self.output.push(self.data);
+                    self.dots = 0;
+                    self.bits = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Unused SC bits are left to the bus, which knows the model.
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.data,
+            0xFF02 => self.control,
+            _ => 0xFF,
+        }
+    }
+
+    /// Every byte the game has started sending since power on.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_completes_after_eight_bits() {
+        let mut serial = Serial::new();
+        serial.write(0xFF01, b'A');
+        serial.write(0xFF02, 0x81);
+
+        for _ in 0..(8 * DOTS_PER_BIT - 1) {
+            assert_eq!(serial.tick(), 0);
+        }
+
+        assert_eq!(serial.tick(), interrupts::SERIAL);
+        assert_eq!(serial.read(0xFF02), 0x01);
+        assert_eq!(serial.read(0xFF01), 0xFF);
+        assert_eq!(serial.output(), b"A");
+    }
+
+    #[test]
+    fn test_external_clock_never_completes() {
+        let mut serial = Serial::new();
+        serial.write(0xFF02, 0x80);
+
+        for _ in 0..(16 * DOTS_PER_BIT) {
+            assert_eq!(serial.tick(), 0);
+        }
+
+        assert_eq!(serial.read(0xFF02), 0x80);
+    }
+}
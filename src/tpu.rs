@@ -1,8 +1,4 @@
-use crate::cpu::CPU;
 use crate::interrupts;
-use std::sync::{LazyLock, Mutex, MutexGuard};
-
-static TIMER: LazyLock<Mutex<Timer>> = LazyLock::new(|| Mutex::new(Timer::new()));
 
 pub struct Timer {
     div: u16,
@@ -13,9 +9,9 @@ pub struct Timer {
 }
 
 impl Timer {
-    fn new() -> Self {
+    pub fn new(div: u16) -> Self {
         Self {
-            div: 0xABCC,
+            div,
             tima: 0,
             tma: 0,
             tac: 0,
@@ -23,54 +19,42 @@ impl Timer {
         }
     }
 
-    pub fn get() -> MutexGuard<'static, Timer> {
-        TIMER.lock().expect("Could not acquire timer lock")
-    }
-
-    pub fn emu_cycles(&mut self, n: u8, cpu: &mut CPU) {
-        for _ in 0..(n * 4) {
-            self.ticks += 1;
-            self.tick(cpu)
-        }
-    }
-
-    fn tick(&mut self, cpu: &mut CPU) {
+    /// Advances the timer by one dot and returns the interrupts it raised.
+    pub fn tick(&mut self) -> u8 {
+        self.ticks += 1;
         let previous_div = self.div;
         self.div = self.div.wrapping_add(1);
 
         let mut update_timer = false;
 
         match self.tac & 0b11 {
-            0b00 => {
-                if (previous_div & (1 << 9)) != 0 && (self.div & (1 << 9)) == 0 {
-                    update_timer = true;
-                }
+            0b00 if (previous_div & (1 << 9)) != 0 && (self.div & (1 << 9)) == 0 => {
+                update_timer = true;
             }
-            0b01 => {
-                if (previous_div & (1 << 3)) != 0 && (self.div & (1 << 3)) == 0 {
-                    update_timer = true;
-                }
+            0b01 if (previous_div & (1 << 3)) != 0 && (self.div & (1 << 3)) == 0 => {
+                update_timer = true;
             }
-            0b10 => {
-                if (previous_div & (1 << 5)) != 0 && (self.div & (1 << 5)) == 0 {
-                    update_timer = true;
-                }
+            0b10 if (previous_div & (1 << 5)) != 0 && (self.div & (1 << 5)) == 0 => {
+                update_timer = true;
             }
-            0b11 => {
-                if (previous_div & (1 << 7)) != 0 && (self.div & (1 << 7)) == 0 {
-                    update_timer = true;
-                }
+            0b11 if (previous_div & (1 << 7)) != 0 && (self.div & (1 << 7)) == 0 => {
+                update_timer = true;
             }
             _ => {}
         }
 
-        if update_timer && (self.tac & (1 << 2) != 0) {
-            if self.tima == 0xFF {
-                self.tima = self.tma;
+        if !update_timer || self.tac & (1 << 2) == 0 {
+            return 0;
+        }
 
-                cpu.request_interrupt(interrupts::TIMER)
-            }
+        if self.tima == 0xFF {
+            self.tima = self.tma;
+
+            return interrupts::TIMER;
         }
+
+        self.tima += 1;
+        0
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
@@ -93,3 +77,35 @@ impl Timer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tima_counts_at_the_selected_rate() {
+        let mut timer = Timer::new(0);
+        timer.write(0xFF04, 0);
+        timer.write(0xFF07, 0b101);
+
+        for _ in 0..16 * 10 {
+            timer.tick();
+        }
+
+        assert_eq!(timer.read(0xFF05), 10);
+    }
+
+    #[test]
+    fn test_overflow_reloads_tma_and_requests_interrupt() {
+        let mut timer = Timer::new(0);
+        timer.write(0xFF04, 0);
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF06, 0x42);
+        timer.write(0xFF07, 0b101);
+
+        let requested: u8 = (0..16).map(|_| timer.tick()).fold(0, |all, it| all | it);
+
+        assert_eq!(requested, interrupts::TIMER);
+        assert_eq!(timer.read(0xFF05), 0x42);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::cpu::Registers;
+use crate::memory::Memory;
+use std::io::{self, BufWriter, Write};
+
+/// When a `Tracer` starts or stops logging.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Trigger {
+    /// The first time the CPU is about to run the instruction at this address.
+    Pc(u16),
+    /// Once this many instructions have run since the tracer was attached.
+    Instructions(u64),
+}
+
+impl Trigger {
+    fn fired(&self, pc: u16, instructions: u64) -> bool {
+        match *self {
+            Trigger::Pc(address) => address == pc,
+            Trigger::Instructions(count) => count == instructions,
+        }
+    }
+}
+
+/// Streams one line per instruction in the Gameboy Doctor format, e.g.
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`.
+///
+/// Lines are logged before the instruction runs. Writes go through a `BufWriter`, which
+/// is flushed when tracing stops or the tracer is dropped.
+pub struct Tracer {
+    sink: BufWriter<Box<dyn Write + Send>>,
+    start: Option<Trigger>,
+    stop: Option<Trigger>,
+    instructions: u64,
+    active: bool,
+    done: bool,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// Traces every instruction to `writer` from the moment the tracer is attached.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            sink: BufWriter::new(Box::new(writer)),
+            start: None,
+            stop: None,
+            instructions: 0,
+            active: true,
+            done: false,
+            error: None,
+        }
+    }
+
+    /// Waits for `trigger` before logging anything. The triggering instruction is logged.
+    pub fn start_at(mut self, trigger: Trigger) -> Self {
+        self.start = Some(trigger);
+        self.active = false;
+        self
+    }
+
+    /// Stops logging for good at `trigger`. The triggering instruction is not logged.
+    pub fn stop_at(mut self, trigger: Trigger) -> Self {
+        self.stop = Some(trigger);
+        self
+    }
+
+    /// Flushes the sink and reports the first write error, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush();
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Called by the CPU right before it runs an instruction.
+    pub(crate) fn trace(&mut self, registers: Registers, memory: &dyn Memory) {
+        let instructions = self.instructions;
+        self.instructions += 1;
+
+        if self.done {
+            return;
+        }
+
+        if self
+            .stop
+            .is_some_and(|stop| stop.fired(registers.pc, instructions))
+        {
+            self.done = true;
+            self.active = false;
+            self.flush();
+            return;
+        }
+
+        if !self.active {
+            match self.start {
+                Some(start) if start.fired(registers.pc, instructions) => self.active = true,
+                _ => return,
+            }
+        }
+
+        let pc = registers.pc;
+        let result = writeln!(
+            self.sink,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            registers.a,
+            registers.f,
+            registers.b,
+            registers.c,
+            registers.d,
+            registers.e,
+            registers.h,
+            registers.l,
+            registers.sp,
+            pc,
+            memory.peek(pc),
+            memory.peek(pc.wrapping_add(1)),
+            memory.peek(pc.wrapping_add(2)),
+            memory.peek(pc.wrapping_add(3)),
+        );
+        self.record(result);
+    }
+
+    fn flush(&mut self) {
+        let result = self.sink.flush();
+        self.record(result);
+    }
+
+    /// Keeps the first error and gives up on tracing, rather than failing the emulation.
+    fn record(&mut self, result: io::Result<()>) {
+        if let Err(error) = result {
+            self.done = true;
+            self.active = false;
+            self.error.get_or_insert(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::Interrupts;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Every byte reads back as the low byte of its address.
+    struct AddressMemory(Interrupts);
+
+    impl Memory for AddressMemory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.peek(address)
+        }
+
+        fn write(&mut self, _: u16, _: u8) {}
+
+        fn peek(&self, address: u16) -> u8 {
+            address as u8
+        }
+
+        fn tick(&mut self, _: u8) {}
+
+        fn stop(&mut self) -> bool {
+            false
+        }
+
+        fn interrupts(&mut self) -> &mut Interrupts {
+            &mut self.0
+        }
+    }
+
+    fn registers(pc: u16) -> Registers {
+        Registers {
+            a: 0x01,
+            f: 0xB0,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xD8,
+            h: 0x01,
+            l: 0x4D,
+            sp: 0xFFFE,
+            pc,
+        }
+    }
+
+    fn run(tracer: &mut Tracer, pcs: impl IntoIterator<Item = u16>) {
+        let memory = AddressMemory(Interrupts::new());
+        for pc in pcs {
+            tracer.trace(registers(pc), &memory);
+        }
+    }
+
+    #[test]
+    fn test_lines_match_gameboy_doctor() {
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::new(buffer.clone());
+
+        run(&mut tracer, [0x0100, 0xFFFE]);
+        tracer.finish().unwrap();
+
+        assert_eq!(
+            buffer.lines(),
+            [
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,01,02,03",
+                "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:FFFE PCMEM:FE,FF,00,01",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_start_and_stop_at_pc() {
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::new(buffer.clone())
+            .start_at(Trigger::Pc(0x0102))
+            .stop_at(Trigger::Pc(0x0104));
+
+        run(
+            &mut tracer,
+            [0x0100, 0x0101, 0x0102, 0x0103, 0x0100, 0x0104, 0x0102],
+        );
+        tracer.finish().unwrap();
+
+        let pcs: Vec<_> = buffer
+            .lines()
+            .iter()
+            .map(|line| line[51..55].to_string())
+            .collect();
+        assert_eq!(pcs, ["0102", "0103", "0100"]);
+    }
+
+    #[test]
+    fn test_start_and_stop_at_instruction_count() {
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::new(buffer.clone())
+            .start_at(Trigger::Instructions(2))
+            .stop_at(Trigger::Instructions(4));
+
+        run(&mut tracer, 0x0100..0x0108);
+        tracer.finish().unwrap();
+
+        let pcs: Vec<_> = buffer
+            .lines()
+            .iter()
+            .map(|line| line[51..55].to_string())
+            .collect();
+        assert_eq!(pcs, ["0102", "0103"]);
+    }
+
+    #[test]
+    fn test_write_errors_stop_tracing_and_are_reported() {
+        let mut tracer = Tracer::new(FailingWriter);
+
+        run(&mut tracer, 0x0100..0x4000);
+
+        assert_eq!(tracer.finish().unwrap_err().to_string(), "disk full");
+    }
+}